@@ -0,0 +1,168 @@
+// Side by side view of one image built for several platforms
+use super::report::AnalysisReport;
+use crate::ofs::utils::size_human;
+use crate::style::bold;
+use std::collections::BTreeMap;
+
+// Only the largest differences are listed
+const MAX_PACKAGES: usize = 20;
+
+pub struct PackageDiff {
+    pub name: String,
+    pub sizes: Vec<Option<u64>>,
+}
+
+impl PackageDiff {
+    fn spread(&self) -> u64 {
+        let sizes = self.sizes.iter().map(|s| s.unwrap_or(0));
+        sizes.clone().max().unwrap_or(0) - sizes.min().unwrap_or(0)
+    }
+}
+
+fn package_size(report: &AnalysisReport) -> u64 {
+    report
+        .managers
+        .iter()
+        .flat_map(|m| m.packages.iter())
+        .map(|p| p.size)
+        .sum()
+}
+
+// Packages which differ in size, or are missing, between the platforms
+pub fn package_differences(reports: &[(String, AnalysisReport)]) -> Vec<PackageDiff> {
+    let mut sizes: BTreeMap<&str, Vec<Option<u64>>> = BTreeMap::new();
+    for (i, (_, report)) in reports.iter().enumerate() {
+        for pkg in report.managers.iter().flat_map(|m| m.packages.iter()) {
+            let entry = sizes
+                .entry(&pkg.name)
+                .or_insert_with(|| vec![None; reports.len()]);
+            entry[i] = Some(entry[i].unwrap_or(0) + pkg.size);
+        }
+    }
+
+    let mut diffs: Vec<PackageDiff> = sizes
+        .into_iter()
+        .map(|(name, sizes)| PackageDiff {
+            name: name.to_string(),
+            sizes,
+        })
+        .filter(|d| d.sizes.windows(2).any(|w| w[0] != w[1]))
+        .collect();
+    diffs.sort_by_key(|d| std::cmp::Reverse(d.spread()));
+    diffs
+}
+
+fn size_or_missing(size: Option<u64>) -> String {
+    match size {
+        Some(size) => size_human(size),
+        None => "-".to_string(),
+    }
+}
+
+pub fn show_comparison(reports: &[(String, AnalysisReport)]) {
+    let smallest = reports
+        .iter()
+        .map(|(_, r)| r.total_space)
+        .min()
+        .unwrap_or(0);
+
+    println!();
+    println!("{}", bold("Platforms:"));
+    println!(
+        "{:<16}  {:>10}  {:>7}  {:>12}  {:>10}",
+        "Platform", "Total size", "Diff", "Wasted Space", "Packages"
+    );
+    for (platform, report) in reports.iter() {
+        let diff = match ((report.total_space - smallest) * 100).checked_div(smallest) {
+            Some(percent) => format!("+{}%", percent),
+            None => "-".to_string(),
+        };
+        println!(
+            "{:<16}  {:>10}  {:>7}  {:>12}  {:>10}",
+            platform,
+            size_human(report.total_space),
+            diff,
+            size_human(report.wasted_space),
+            size_human(package_size(report))
+        );
+    }
+
+    let diffs = package_differences(reports);
+    if diffs.is_empty() {
+        return;
+    }
+
+    println!();
+    println!("{}", bold("Package size differences:"));
+    print!("{:<32}", "Package");
+    for (platform, _) in reports.iter() {
+        print!("  {:>16}", platform);
+    }
+    println!();
+    for d in diffs.iter().take(MAX_PACKAGES) {
+        print!("{:<32}", d.name);
+        for size in d.sizes.iter() {
+            print!("  {:>16}", size_or_missing(*size));
+        }
+        println!();
+    }
+    if diffs.len() > MAX_PACKAGES {
+        println!("... and {} more", diffs.len() - MAX_PACKAGES);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::analysis::compare::package_differences;
+    use crate::analysis::pkg_manager::Manager;
+    use crate::analysis::report::AnalysisReport;
+    use crate::ofs::ofs::OverlayFs;
+    use crate::packages::deps::Package;
+
+    fn report(packages: &[(&str, u64)]) -> AnalysisReport {
+        let packages = packages
+            .iter()
+            .map(|(name, size)| Package {
+                name: name.to_string(),
+                version: "1".to_string(),
+                size: *size,
+                optional: false,
+            })
+            .collect();
+        AnalysisReport {
+            score: 100,
+            wasted_space: 0,
+            total_space: 0,
+            ofs: OverlayFs::new(),
+            image: "app".to_string(),
+            managers: vec![Manager {
+                name: "apk".to_string(),
+                packages,
+                cache: None,
+                waste_size: 0,
+            }],
+            dup_files: vec![],
+            same_content: vec![],
+            metadata_rewrites: vec![],
+        }
+    }
+
+    #[test]
+    fn lists_packages_differing_between_platforms() {
+        let reports = vec![
+            (
+                "linux/amd64".to_string(),
+                report(&[("musl", 100), ("zlib", 50), ("libgcc", 10)]),
+            ),
+            (
+                "linux/arm64".to_string(),
+                report(&[("musl", 100), ("zlib", 80), ("qemu", 500)]),
+            ),
+        ];
+
+        let diffs = package_differences(&reports);
+        let names: Vec<&str> = diffs.iter().map(|d| d.name.as_str()).collect();
+        assert_eq!(names, vec!["qemu", "zlib", "libgcc"]);
+        assert_eq!(diffs[0].sizes, vec![None, Some(500)]);
+    }
+}
@@ -0,0 +1,146 @@
+// What a container added on top of its image
+use super::report::AnalysisReport;
+use crate::docker::container::{Change, ChangeKind};
+use crate::ofs::layer::CONTAINER_LAYER;
+use crate::ofs::ofs::OverlayFs;
+use crate::ofs::utils::size_human;
+use crate::style::bold;
+
+// Only the largest files are listed
+const MAX_FILES: usize = 20;
+
+// Files written by the container with the size of the container's version, largest first
+pub fn container_files(ofs: &OverlayFs) -> Vec<(String, u64)> {
+    let mut files: Vec<(String, u64)> = ofs
+        .entries()
+        .filter_map(|f| {
+            let size = match f.versions.iter().rev().find(|v| !v.deleted) {
+                Some(v) if f.layer_name(v.layer) == CONTAINER_LAYER => v.size,
+                None if f.layer_id() == CONTAINER_LAYER => f.size,
+                _ => return None,
+            };
+            Some((f.path(), size))
+        })
+        .collect();
+    files.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    files
+}
+
+fn count(changes: &[Change], kind: ChangeKind) -> usize {
+    changes.iter().filter(|c| c.kind == kind).count()
+}
+
+fn percent_of(size: u64, total: u64) -> String {
+    match (size * 100).checked_div(total) {
+        Some(percent) => format!("+{}%", percent),
+        None => "-".to_string(),
+    }
+}
+
+pub fn show_container_report(
+    container: &AnalysisReport,
+    image_size: u64,
+    image_waste: u64,
+    changes: &[Change],
+) {
+    let written = container
+        .ofs
+        .layer_sizes()
+        .get(CONTAINER_LAYER)
+        .copied()
+        .unwrap_or(0);
+
+    println!();
+    println!("{}", bold("Container:"));
+    println!("  Image size: {}", size_human(image_size));
+    println!(
+        "  Written by the container: {} ({} of the image)",
+        size_human(written),
+        percent_of(written, image_size)
+    );
+    println!(
+        "  Changes: {} added, {} modified, {} deleted",
+        count(changes, ChangeKind::Added),
+        count(changes, ChangeKind::Modified),
+        count(changes, ChangeKind::Deleted)
+    );
+    println!(
+        "  Wasted Space: {} (image {})",
+        size_human(container.wasted_space),
+        size_human(image_waste)
+    );
+
+    let files = container_files(&container.ofs);
+    if files.is_empty() {
+        return;
+    }
+    println!();
+    println!("{}", bold("Largest files written by the container:"));
+    println!("{:>10}  File Path", "Size");
+    for (path, size) in files.iter().take(MAX_FILES) {
+        println!("{:>10}  {}", size_human(*size), path);
+    }
+    if files.len() > MAX_FILES {
+        println!("... and {} more", files.len() - MAX_FILES);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::analysis::container::container_files;
+    use crate::docker::container::parse_diff;
+    use crate::image::image_tar::{apply_layer, parse_container_export};
+    use crate::ofs::layer::CONTAINER_LAYER;
+    use crate::ofs::ofs::OverlayFs;
+    use tar::{Builder, EntryType, Header};
+
+    fn append(builder: &mut Builder<Vec<u8>>, path: &str, size: usize) {
+        let mut header = Header::new_gnu();
+        if path.ends_with('/') {
+            header.set_entry_type(EntryType::Directory);
+        }
+        header.set_size(size as u64);
+        header.set_mode(0o644);
+        builder
+            .append_data(&mut header, path, vec![0; size].as_slice())
+            .unwrap();
+    }
+
+    #[test]
+    fn adds_container_changes_as_top_layer() {
+        let mut ofs = OverlayFs::new();
+        ofs.add_file("bin/busybox", 100, "layer0");
+        ofs.add_file("etc/hosts", 10, "layer0");
+        ofs.add_file("etc/motd", 20, "layer0");
+
+        // docker export writes the whole filesystem of the container
+        let mut builder = Builder::new(vec![]);
+        append(&mut builder, "bin/", 0);
+        append(&mut builder, "bin/busybox", 100);
+        append(&mut builder, "etc/", 0);
+        append(&mut builder, "etc/hosts", 50);
+        append(&mut builder, "var/", 0);
+        append(&mut builder, "var/log/", 0);
+        append(&mut builder, "var/log/app.log", 300);
+        let export = builder.into_inner().unwrap();
+
+        let changes =
+            parse_diff("C /etc\nC /etc/hosts\nD /etc/motd\nA /var/log\nA /var/log/app.log\n")
+                .unwrap();
+        let entries = parse_container_export(export.as_slice(), &changes, false).unwrap();
+        apply_layer(&mut ofs, &entries, CONTAINER_LAYER).unwrap();
+        ofs.update_sizes();
+
+        let files = container_files(&ofs);
+        assert_eq!(
+            files,
+            vec![
+                ("var/log/app.log".to_string(), 300),
+                ("etc/hosts".to_string(), 50)
+            ]
+        );
+        assert!(ofs.get_dir("/var/log").is_some());
+        assert!(ofs.get_file("/etc/motd").unwrap().versions[0].deleted);
+        assert_eq!(ofs.layer_sizes()[CONTAINER_LAYER], 350);
+    }
+}
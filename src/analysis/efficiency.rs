@@ -1,5 +1,7 @@
+use crate::ofs::file_info::{FileInfo, Metadata};
 use crate::ofs::ofs::OverlayFs;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
 #[derive(Serialize, Deserialize)]
 pub struct Info {
@@ -8,8 +10,19 @@ pub struct Info {
     pub wasted_size: u64,
 }
 
+// Files with the same content under different paths, only found with content hashes
+#[derive(Serialize, Deserialize)]
+pub struct SameContent {
+    pub digest: String,
+    pub size: u64,
+    pub paths: Vec<String>,
+    pub wasted_size: u64,
+}
+
 pub struct Efficiency {
     duplicates: Vec<Info>,
+    same_content: Vec<SameContent>,
+    rewrites: Vec<Info>,
 }
 
 pub fn list_multiple_versions(ofs: &OverlayFs) -> Vec<Info> {
@@ -24,49 +37,176 @@ pub fn list_multiple_versions(ofs: &OverlayFs) -> Vec<Info> {
                 file.total_size - ver.size
             };
             let i = Info {
-                path: format!("{}{}", file.path, file.name),
+                path: file.path(),
                 count: file.versions.len() + 1,
                 wasted_size: wasted,
             };
             multiple_versions.push(i);
         }
     }
-    multiple_versions.sort_by(|a, b| b.wasted_size.cmp(&a.wasted_size));
+    multiple_versions.sort_by_key(|i| std::cmp::Reverse(i.wasted_size));
     multiple_versions
 }
 
+// Size and metadata of every version still in the image, oldest first
+fn versions(file: &FileInfo) -> Vec<Option<(u64, Option<&Metadata>)>> {
+    let mut versions = vec![Some((file.size, file.metadata.as_deref()))];
+    for v in file.versions.iter() {
+        versions.push(if v.deleted {
+            None
+        } else {
+            Some((v.size, v.metadata.as_deref()))
+        });
+    }
+    versions
+}
+
+fn digest(metadata: Option<&Metadata>) -> Option<&str> {
+    metadata.and_then(|m| m.digest.as_deref())
+}
+
+// The same bytes visible under several paths, any layer. Links and empty files
+// are left out, they take no space.
+pub fn list_same_content(ofs: &OverlayFs) -> Vec<SameContent> {
+    let mut by_digest: BTreeMap<&str, (u64, Vec<String>)> = BTreeMap::new();
+    for file in ofs.entries() {
+        if file.link.is_some() {
+            continue;
+        }
+        if let Some(Some((size, metadata))) = versions(file.info()).last() {
+            if let (Some(digest), true) = (digest(*metadata), *size > 0) {
+                let same = by_digest.entry(digest).or_insert((*size, vec![]));
+                same.1.push(file.path());
+            }
+        }
+    }
+
+    let mut same_content: Vec<SameContent> = by_digest
+        .into_iter()
+        .filter(|(_, (_, paths))| paths.len() > 1)
+        .map(|(digest, (size, mut paths))| {
+            paths.sort();
+            SameContent {
+                digest: digest.to_string(),
+                size,
+                wasted_size: size * (paths.len() as u64 - 1),
+                paths,
+            }
+        })
+        .collect();
+    same_content.sort_by_key(|s| std::cmp::Reverse(s.wasted_size));
+    same_content
+}
+
+// Files written again with the same content, by a chmod or chown in a later
+// layer. Every rewrite stores the whole file once more.
+pub fn list_metadata_rewrites(ofs: &OverlayFs) -> Vec<Info> {
+    let mut rewrites = vec![];
+    for file in ofs.entries() {
+        let versions = versions(file.info());
+        let mut count = 0;
+        let mut wasted = 0;
+        for pair in versions.windows(2) {
+            if let [Some((_, before)), Some((size, after))] = pair {
+                if digest(*before).is_some() && digest(*before) == digest(*after) {
+                    count += 1;
+                    wasted += size;
+                }
+            }
+        }
+        if count > 0 {
+            rewrites.push(Info {
+                path: file.path(),
+                count,
+                wasted_size: wasted,
+            });
+        }
+    }
+    rewrites.sort_by_key(|i| std::cmp::Reverse(i.wasted_size));
+    rewrites
+}
+
 impl Efficiency {
     pub fn new(ofs: &OverlayFs) -> Efficiency {
         Efficiency {
             duplicates: list_multiple_versions(ofs),
+            same_content: list_same_content(ofs),
+            rewrites: list_metadata_rewrites(ofs),
         }
     }
 
+    // Rewrites are already counted with the other versions of their path
     pub fn get_wasted_bytes(&self) -> u64 {
-        self.duplicates.iter().map(|i| i.wasted_size).sum()
+        let versions: u64 = self.duplicates.iter().map(|i| i.wasted_size).sum();
+        let same_content: u64 = self.same_content.iter().map(|s| s.wasted_size).sum();
+        versions + same_content
     }
 
-    pub fn get_duplicates(self) -> Vec<Info> {
-        self.duplicates
+    pub fn get_duplicates(self) -> (Vec<Info>, Vec<SameContent>, Vec<Info>) {
+        (self.duplicates, self.same_content, self.rewrites)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::analysis::efficiency::list_multiple_versions;
-    use crate::ofs::directory::ODirectory;
-    use crate::ofs::file_info::FileInfo;
+    use crate::analysis::efficiency::{list_multiple_versions, Efficiency};
+    use crate::image::image_tar::{apply_layer, LayerEntry};
+    use crate::ofs::file_info::Metadata;
     use crate::ofs::ofs::OverlayFs;
 
+    fn file(path: &str, size: u64, mode: u32, digest: &str) -> LayerEntry {
+        let metadata = Metadata {
+            mode,
+            digest: Some(digest.to_string()),
+            ..Metadata::default()
+        };
+        LayerEntry::File(path.to_string(), size, metadata)
+    }
+
     #[test]
     fn list_all_multi_version() {
-        let ofs_json = r#"{"root":{"name":"/","size":0,"files":{"file1":{"name":"file1","size":400,"layer_id":"lay1","path":"","total_size":450,"versions":[{"deleted":false,"size":50,"layer_id":"lay2"}]}},"directories":{},"deleted":false},"layers":{}}"#;
+        let ofs_json = r#"{"names":["/","file1"],"layer_ids":["lay1","lay2"],"dirs":[{"parent":4294967295,"name":0,"size":0,"first_dir":4294967295,"first_file":0,"next":4294967295}],"files":[{"dir":0,"name":1,"next":4294967295,"size":400,"layer":0,"total_size":450,"versions":[{"deleted":false,"size":50,"layer":1}]}],"layers":{}}"#;
 
-        let ofs: OverlayFs = serde_json::from_str(&ofs_json).unwrap();
+        let ofs: OverlayFs = serde_json::from_str(ofs_json).unwrap();
 
         let infos = list_multiple_versions(&ofs);
         assert_eq!(infos.len(), 1);
         assert_eq!(infos[0].path, "file1");
         assert_eq!(infos[0].wasted_size, 400);
+        assert_eq!(ofs.get_file("/file1").unwrap().layer_id(), "lay1");
+    }
+
+    #[test]
+    fn finds_same_content_and_rewrites() {
+        let mut ofs = OverlayFs::new();
+        let base = vec![
+            file("usr/lib/libfoo.so", 1000, 0o644, "sha256:foo"),
+            file("app/vendor/libfoo.so", 1000, 0o644, "sha256:foo"),
+            file("bin/tool", 500, 0o644, "sha256:tool"),
+            file("etc/a", 0, 0o644, "sha256:empty"),
+            file("etc/b", 0, 0o644, "sha256:empty"),
+        ];
+        apply_layer(&mut ofs, &base, "layer0").unwrap();
+        // RUN chmod +x /bin/tool
+        apply_layer(
+            &mut ofs,
+            &[file("bin/tool", 500, 0o755, "sha256:tool")],
+            "layer1",
+        )
+        .unwrap();
+        ofs.update_sizes();
+
+        let (duplicates, same_content, rewrites) = Efficiency::new(&ofs).get_duplicates();
+        assert_eq!(duplicates.len(), 1);
+        assert_eq!(same_content.len(), 1);
+        assert_eq!(
+            same_content[0].paths,
+            vec!["app/vendor/libfoo.so", "usr/lib/libfoo.so"]
+        );
+        assert_eq!(same_content[0].wasted_size, 1000);
+        assert_eq!(rewrites.len(), 1);
+        assert_eq!(rewrites[0].path, "bin/tool");
+        assert_eq!(rewrites[0].wasted_size, 500);
+        assert_eq!(Efficiency::new(&ofs).get_wasted_bytes(), 1500);
     }
 }
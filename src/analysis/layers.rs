@@ -0,0 +1,215 @@
+// What each layer added, modified and deleted, like `docker diff` for every build step
+use crate::docker::container::ChangeKind;
+use crate::error::{Error, Result};
+use crate::ofs::ofs::OverlayFs;
+use crate::ofs::utils::size_human;
+use crate::style::bold;
+use std::collections::HashMap;
+
+// A deleted file counts the bytes of the version it hid
+#[derive(Debug, PartialEq)]
+pub struct LayerChange {
+    pub path: String,
+    pub kind: ChangeKind,
+    pub size: u64,
+}
+
+#[derive(Default)]
+pub struct ChangeSummary {
+    pub files: usize,
+    pub added: u64,
+    pub modified: u64,
+    pub removed: u64,
+}
+
+impl ChangeSummary {
+    pub fn new(changes: &[LayerChange]) -> ChangeSummary {
+        let mut summary = ChangeSummary::default();
+        for c in changes {
+            match c.kind {
+                ChangeKind::Added => summary.added += c.size,
+                ChangeKind::Modified => summary.modified += c.size,
+                ChangeKind::Deleted => summary.removed += c.size,
+            }
+            if c.kind != ChangeKind::Deleted {
+                summary.files += 1;
+            }
+        }
+        summary
+    }
+}
+
+// Changes by layer id, sorted by path. A file written again after it was
+// deleted counts as added.
+pub fn layer_changes(ofs: &OverlayFs) -> HashMap<&str, Vec<LayerChange>> {
+    let mut changes: HashMap<&str, Vec<LayerChange>> = HashMap::new();
+    for f in ofs.entries() {
+        let path = format!("/{}", f.path());
+        let mut versions = vec![(f.layer_id(), f.size, false)];
+        for v in f.versions.iter() {
+            versions.push((f.layer_name(v.layer), v.size, v.deleted));
+        }
+
+        let mut visible: Option<u64> = None;
+        for (layer, size, deleted) in versions {
+            let change = match (deleted, visible) {
+                (true, Some(hidden)) => (ChangeKind::Deleted, hidden),
+                (true, None) => continue,
+                (false, Some(_)) => (ChangeKind::Modified, size),
+                (false, None) => (ChangeKind::Added, size),
+            };
+            visible = if deleted { None } else { Some(size) };
+            let layer_changes = changes.entry(layer).or_default();
+            // An opaque directory hides the file and the same layer writes it again
+            if let Some(last) = layer_changes.last_mut() {
+                if last.path == path && last.kind == ChangeKind::Deleted && !deleted {
+                    last.kind = ChangeKind::Modified;
+                    last.size = size;
+                    continue;
+                }
+            }
+            layer_changes.push(LayerChange {
+                path: path.clone(),
+                kind: change.0,
+                size: change.1,
+            });
+        }
+    }
+    for layer in changes.values_mut() {
+        layer.sort_by(|a, b| a.path.cmp(&b.path));
+    }
+    changes
+}
+
+fn marker(kind: ChangeKind) -> &'static str {
+    match kind {
+        ChangeKind::Added => "A",
+        ChangeKind::Modified => "C",
+        ChangeKind::Deleted => "D",
+    }
+}
+
+pub fn show_layers(ofs: &OverlayFs) {
+    let changes = layer_changes(ofs);
+    println!();
+    println!("{}", bold("Layers:"));
+    println!("Layer        Size    Files       Added    Modified     Removed  Created By");
+    for l in ofs.get_layers().iter().filter(|l| !l.empty_layer) {
+        let summary = changes
+            .get(l.layer_id.as_str())
+            .map(|c| ChangeSummary::new(c))
+            .unwrap_or_default();
+        println!(
+            "{:>5}  {:>10}  {:>7}  {:>10}  {:>10}  {:>10}  {}",
+            l.index,
+            size_human(summary.added + summary.modified),
+            summary.files,
+            size_human(summary.added),
+            size_human(summary.modified),
+            size_human(summary.removed),
+            l.instruction()
+        );
+    }
+}
+
+// The layer is picked by the number `cake layers` shows
+pub fn show_layer_changes(ofs: &OverlayFs, index: usize) -> Result<()> {
+    let layer = ofs
+        .get_layers()
+        .into_iter()
+        .find(|l| l.index == index)
+        .ok_or_else(|| Error::Invalid(format!("No layer {} in the image", index)))?;
+    let changes = layer_changes(ofs);
+    let changes = changes
+        .get(layer.layer_id.as_str())
+        .map(|c| c.as_slice())
+        .unwrap_or_default();
+    let summary = ChangeSummary::new(changes);
+
+    println!();
+    println!(
+        "{} {}",
+        bold(&format!("Layer {}:", index)),
+        layer.instruction()
+    );
+    println!(
+        "  Files: {}, added {}, modified {}, removed {}",
+        summary.files,
+        size_human(summary.added),
+        size_human(summary.modified),
+        size_human(summary.removed)
+    );
+    println!();
+    for c in changes {
+        println!("{} {}", marker(c.kind), c.path);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::analysis::layers::{layer_changes, LayerChange};
+    use crate::docker::container::ChangeKind;
+    use crate::ofs::ofs::OverlayFs;
+
+    fn change(path: &str, kind: ChangeKind, size: u64) -> LayerChange {
+        LayerChange {
+            path: path.to_string(),
+            kind,
+            size,
+        }
+    }
+
+    #[test]
+    fn lists_changes_of_every_layer() {
+        let mut ofs = OverlayFs::new();
+        ofs.add_file("etc/motd", 10, "layer0");
+        ofs.add_file("usr/bin/git", 300, "layer0");
+        ofs.add_file("etc/motd", 20, "layer1");
+        ofs.add_file("usr/bin/.wh.git", 0, "layer1");
+        ofs.add_file("tmp/build.log", 50, "layer1");
+        ofs.add_file("usr/bin/git", 400, "layer2");
+
+        let changes = layer_changes(&ofs);
+        assert_eq!(
+            changes["layer0"],
+            vec![
+                change("/etc/motd", ChangeKind::Added, 10),
+                change("/usr/bin/git", ChangeKind::Added, 300)
+            ]
+        );
+        assert_eq!(
+            changes["layer1"],
+            vec![
+                change("/etc/motd", ChangeKind::Modified, 20),
+                change("/tmp/build.log", ChangeKind::Added, 50),
+                change("/usr/bin/git", ChangeKind::Deleted, 300)
+            ]
+        );
+        assert_eq!(
+            changes["layer2"],
+            vec![change("/usr/bin/git", ChangeKind::Added, 400)]
+        );
+    }
+
+    // rm -rf /var/lib/apt/lists && apt-get update, like the opaque whiteout layer in image_tar
+    #[test]
+    fn shows_files_written_again_below_an_opaque_whiteout_once() {
+        let mut ofs = OverlayFs::new();
+        ofs.add_file("var/lib/apt/lists/main", 100, "layer0");
+        ofs.add_file("var/lib/apt/lists/security", 200, "layer0");
+        ofs.add_file("var/lib/apt/lists/.wh..wh..opq", 0, "layer1");
+        ofs.add_file("var/lib/apt/lists/main", 50, "layer1");
+        ofs.add_file("var/lib/apt/lists/universe", 70, "layer1");
+
+        let changes = layer_changes(&ofs);
+        assert_eq!(
+            changes["layer1"],
+            vec![
+                change("/var/lib/apt/lists/main", ChangeKind::Modified, 50),
+                change("/var/lib/apt/lists/security", ChangeKind::Deleted, 200),
+                change("/var/lib/apt/lists/universe", ChangeKind::Added, 70)
+            ]
+        );
+    }
+}
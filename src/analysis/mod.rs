@@ -1,3 +1,6 @@
+pub mod compare;
+pub mod container;
 mod efficiency;
+pub mod layers;
 pub mod pkg_manager;
 pub mod report;
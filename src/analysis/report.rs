@@ -1,11 +1,9 @@
 use super::pkg_manager::Manager;
-use crate::analysis::efficiency::{Efficiency, Info};
+use crate::analysis::efficiency::{Efficiency, Info, SameContent};
 use crate::ofs::ofs::OverlayFs;
 use crate::ofs::utils::size_human;
 use crate::style::{bold, green, red, yellow};
 use serde::{Deserialize, Serialize};
-use std::fs::File;
-use std::io::{BufReader, Read, Write};
 
 #[derive(Serialize, Deserialize)]
 pub struct AnalysisReport {
@@ -17,6 +15,11 @@ pub struct AnalysisReport {
 
     pub managers: Vec<Manager>,
     pub dup_files: Vec<Info>,
+    // Only filled when the content of files was hashed
+    #[serde(default)]
+    pub same_content: Vec<SameContent>,
+    #[serde(default)]
+    pub metadata_rewrites: Vec<Info>,
 }
 
 impl AnalysisReport {
@@ -30,8 +33,12 @@ impl AnalysisReport {
         let pkg_waste: u64 = managers.iter().map(|m| m.waste_size).sum();
         let size = ofs.size();
 
-        let score = ((size - (waste + pkg_waste)) * 100) / size;
-        let dup_files = eff.get_duplicates();
+        // An empty root filesystem wastes nothing
+        let score = match size {
+            0 => 100,
+            size => (size.saturating_sub(waste + pkg_waste) * 100) / size,
+        };
+        let (dup_files, same_content, metadata_rewrites) = eff.get_duplicates();
 
         AnalysisReport {
             score,
@@ -41,25 +48,11 @@ impl AnalysisReport {
             image: image.to_string(),
             managers,
             dup_files,
+            same_content,
+            metadata_rewrites,
         }
     }
 
-    pub fn save_report_as_json(&self) {
-        let result = serde_json::to_string(&self).unwrap();
-        let mut file = File::create(format!("{}_report.json", self.image)).unwrap();
-        file.write_all(result.as_ref()).unwrap();
-    }
-
-    pub fn create_report_from_json(image: &str) -> AnalysisReport {
-        let file = File::open(format!("{}_report.json", image)).unwrap();
-        let mut buf_reader = BufReader::new(file);
-
-        let mut contents = String::new();
-        buf_reader.read_to_string(&mut contents).unwrap();
-
-        serde_json::from_str(&contents).unwrap()
-    }
-
     pub fn show_report(&self) {
         println!();
         println!("{}", bold("Analysis Report:"));
@@ -67,6 +60,20 @@ impl AnalysisReport {
         println!("  Total size: {}", size_human(self.total_space),);
         println!("  Wasted Space: {}", size_human(self.wasted_space),);
 
+        println!();
+        println!("{}", bold("Layers:"));
+        println!("Layer        Size  Created By");
+        let sizes = self.ofs.layer_sizes();
+        for l in self.ofs.get_layers().iter().filter(|l| !l.empty_layer) {
+            let size = sizes.get(l.layer_id.as_str()).copied().unwrap_or(0);
+            println!(
+                "{:>5}  {:>10}  {}",
+                l.index,
+                size_human(size),
+                l.instruction()
+            );
+        }
+
         println!();
         println!("{}", bold("Inefficient Files:"));
         println!("Count  Wasted Space  File Path");
@@ -79,6 +86,34 @@ impl AnalysisReport {
             );
         }
 
+        if !self.same_content.is_empty() {
+            println!();
+            println!("{}", bold("Same Content:"));
+            println!("Count  Wasted Space  File Paths");
+            for s in self.same_content.iter() {
+                println!(
+                    "{:>5}  {:>12}  {}",
+                    s.paths.len(),
+                    size_human(s.wasted_size),
+                    s.paths.join(", ")
+                );
+            }
+        }
+
+        if !self.metadata_rewrites.is_empty() {
+            println!();
+            println!("{}", bold("Rewritten With The Same Content:"));
+            println!("Count  Wasted Space  File Path");
+            for i in self.metadata_rewrites.iter() {
+                println!(
+                    "{:>5}  {:>12}  {}",
+                    i.count,
+                    size_human(i.wasted_size),
+                    i.path
+                );
+            }
+        }
+
         println!();
         println!("{}", bold("Packages:"));
         for mngr in self.managers.iter() {
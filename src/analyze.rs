@@ -1,96 +1,716 @@
-use log::{info, trace};
+use log::{info, trace, warn};
 
+use crate::analysis::compare::show_comparison;
+use crate::analysis::container::show_container_report;
+use crate::analysis::layers::{show_layer_changes, show_layers};
 use crate::analysis::pkg_manager::Manager;
 use crate::analysis::report::AnalysisReport;
-use crate::docker;
-use crate::image::image_tar::{extract_image_tar, read_tar_layer};
-use crate::image::inspect::ImageInspect;
+use crate::cache;
+use crate::docker::container::Change;
+use crate::docker::source::{self, ImageSource};
+use crate::error::{Context, Error, Result};
+use crate::image::archive::{self, is_compressed_file, ImageArchive};
+use crate::image::config::ImageConfig;
+use crate::image::digest::{is_sha256_digest, sha256_digest, verify_digest};
+use crate::image::image_tar::{apply_layer, parse_container_export, parse_tar_layer, ParsedLayer};
 use crate::image::manifest::Manifest;
-use crate::ofs::layer::Layer;
+use crate::image::oci::{blob_digest, Platform, INDEX_FILE};
+use crate::image::rootfs;
+use crate::image::store::{DirStore, ImageStore};
+use crate::ofs::directory::TreeOptions;
+use crate::ofs::layer::{Layer, CONTAINER_LAYER};
 use crate::ofs::ofs::OverlayFs;
 use crate::packages::alpine::AlpinePackageManager;
 use crate::packages::apt::DebianPackageManager;
 use crate::packages::archive::ArchiveManager;
 use crate::packages::deps::PackageManager;
 use crate::packages::rpm::RPMPackageManager;
+use crate::registry;
+use crate::style::bold;
+use crate::tui;
+use crate::workspace::{Entry, Workspace};
+use std::collections::HashMap;
+use std::fs::{self, File};
 use std::path::Path;
+use std::time::UNIX_EPOCH;
+use tempfile::NamedTempFile;
 
-pub fn analyze_image(image: &str, pkgs: bool, tree: bool) {
-    let report = match read_report(image) {
-        Some(report) => report,
-        None => {
-            let overlayfs = create_ofs(image);
-            create_analysis_report(overlayfs, image)
+pub const REGISTRY_SOURCE: &str = "registry";
+
+// Which images of a multi image archive to analyze
+pub enum Selection<'a> {
+    First,
+    Image(&'a str),
+    All,
+}
+
+// Which platforms of a multi-arch image to analyze
+pub enum Platforms {
+    Default,
+    Only(Platform),
+    All,
+}
+
+// What to read and what to show
+pub struct Options {
+    pub pkgs: bool,
+    pub tree: Option<TreeView>,
+    pub interactive: bool,
+    // Hash the content of every file, needs to read every byte of the image
+    pub hash: bool,
+    pub layers: Option<LayerView>,
+}
+
+// Shown instead of the report by `cake tree`
+pub struct TreeView {
+    pub path: String,
+    pub options: TreeOptions,
+}
+
+// Shown instead of the report by `cake layers`
+pub enum LayerView {
+    All,
+    Changes(usize),
+}
+
+// Layers parsed so far, keyed by where they are stored
+type ParsedLayers = HashMap<(String, Option<u64>), ParsedLayer>;
+
+pub fn analyze_image(
+    image: &str,
+    source: &str,
+    workspace: &Workspace,
+    selection: &Selection,
+    platforms: &Platforms,
+    options: &Options,
+) -> Result<()> {
+    if rootfs::is_rootfs(Path::new(image)) {
+        return analyze_rootfs(image, workspace, options);
+    }
+    if Path::new(image).exists() {
+        return analyze_local_image(image, workspace, selection, platforms, options);
+    }
+    if !matches!(selection, Selection::First) {
+        warn!("Selecting images only applies to image archives and directories");
+    }
+
+    match platforms {
+        Platforms::All => {
+            // A runtime only keeps one platform of an image, the index lives in the registry
+            if source != source::AUTO && source != REGISTRY_SOURCE {
+                return Err(Error::Invalid(
+                    "Comparing platforms needs --source registry".to_string(),
+                ));
+            }
+            let platforms = registry::platforms(image)?;
+
+            let mut reports = vec![];
+            for platform in platforms.iter() {
+                let report =
+                    analyze_remote_image(image, None, Some(platform), workspace, options.hash)?;
+                reports.push((platform.to_string(), report));
+            }
+            show_comparison(&reports);
         }
+        Platforms::Only(platform) => {
+            let source = image_source(source);
+            let report = analyze_remote_image(
+                image,
+                source.as_deref(),
+                Some(platform),
+                workspace,
+                options.hash,
+            )?;
+            show_report(&report, options)?;
+        }
+        Platforms::Default => {
+            let source = image_source(source);
+            let report =
+                analyze_remote_image(image, source.as_deref(), None, workspace, options.hash)?;
+            show_report(&report, options)?;
+        }
+    }
+    Ok(())
+}
+
+fn analyze_remote_image(
+    image: &str,
+    source: Option<&dyn ImageSource>,
+    platform: Option<&Platform>,
+    workspace: &Workspace,
+    hash: bool,
+) -> Result<AnalysisReport> {
+    let mut digest = resolve_digest(image, source)?;
+    let mut name = image.to_string();
+    if let Some(platform) = platform {
+        // Tags of multi-arch images point to the index, not to one image
+        digest = sha256_digest(format!("{}:{}", digest, platform).as_bytes());
+        name = format!("{} ({})", image, platform);
+    }
+    trace!("Image {} resolved to {}", name, digest);
+
+    let entry = cache_entry(workspace, &name, &digest, hash)?;
+    get_report(&entry, image, source, |ofs| {
+        read_remote_image(ofs, image, source, platform, &entry, hash)
+    })
+}
+
+// A root filesystem is analyzed as an image with a single layer
+fn analyze_rootfs(image: &str, workspace: &Workspace, options: &Options) -> Result<()> {
+    info!("Analyzing {} as a root filesystem", image);
+    let path = Path::new(image);
+    // A directory can change at any time without a cheap way to notice, read it every time
+    let temporary;
+    let (workspace, digest) = if path.is_dir() {
+        temporary = Workspace::temporary().context("Failed to create a temporary directory")?;
+        (&temporary, sha256_digest(image.as_bytes()))
+    } else {
+        (workspace, resolve_digest(image, None)?)
     };
 
-    if tree {
-        report.ofs.show_as_tree();
-        return;
+    let entry = cache_entry(workspace, image, &digest, options.hash)?;
+    let report = get_report(&entry, image, None, |ofs| {
+        read_rootfs(ofs, image, options.hash)
+    })?;
+    show_report(&report, options)?;
+    Ok(())
+}
+
+fn read_rootfs(overlayfs: &mut OverlayFs, image: &str, hash: bool) -> Result<()> {
+    let layer = Layer::new(0, image.to_string());
+    let entries = if Path::new(image).is_dir() {
+        rootfs::walk_dir(Path::new(image), hash)?
+    } else {
+        parse_tar_layer(&layer, hash)?.entries
+    };
+    apply_layer(overlayfs, &entries, &layer.layer_id)?;
+    overlayfs.add_layer(layer);
+    Ok(())
+}
+
+// The container's changes become one more layer on top of its image. The
+// image report is cached as usual, the container layer is read every time.
+pub fn analyze_container(
+    id: &str,
+    source: &str,
+    workspace: &Workspace,
+    options: &Options,
+) -> Result<()> {
+    let source = image_source(source).ok_or_else(|| {
+        Error::Invalid("Analyzing a container needs a container runtime".to_string())
+    })?;
+    let source = source.as_ref();
+    let container = source.inspect_container(id)?;
+    let name = container.image_name().to_string();
+    trace!("Container {} runs image {}", id, container.image);
+
+    let image = analyze_remote_image(
+        &container.image,
+        Some(source),
+        None,
+        workspace,
+        options.hash,
+    )?;
+    let changes = source.container_changes(id)?;
+
+    // The export holds the whole filesystem, it is only needed while reading
+    let temporary = Workspace::temporary().context("Failed to create a temporary directory")?;
+    let entry = temporary
+        .entry(&sha256_digest(id.as_bytes()))
+        .context("Failed to create export directory")?;
+    let export = entry.image_tar();
+    let export = export
+        .to_str()
+        .ok_or_else(|| Error::Invalid(format!("Invalid export path {:?}", export)))?;
+    info!("Exporting container {}", id);
+    source.export_container(id, export)?;
+
+    let (image_size, image_waste) = (image.total_space, image.wasted_space);
+    let report = create_container_report(image, export, id, &changes, Some(source), options.hash)?;
+    println!("{}", bold(&format!("Container: {} ({})", id, name)));
+    show_report(&report, options)?;
+    show_container_report(&report, image_size, image_waste, &changes);
+    Ok(())
+}
+
+// The image layers are removed once the image report is cached, the packages
+// are read from the export which holds the whole filesystem of the container
+fn create_container_report(
+    image: AnalysisReport,
+    export: &str,
+    id: &str,
+    changes: &[Change],
+    source: Option<&dyn ImageSource>,
+    hash: bool,
+) -> Result<AnalysisReport> {
+    let file = File::open(export).context(export)?;
+    let entries =
+        parse_container_export(file, changes, hash).map_err(|e| Error::corrupt(export, e))?;
+    let mut ofs = image.ofs;
+    let index = ofs.get_layers().last().map(|l| l.index + 1).unwrap_or(0);
+    apply_layer(&mut ofs, &entries, CONTAINER_LAYER)?;
+    ofs.read_layers_from(export);
+    ofs.add_layer(Layer::container(index, export.to_string(), id));
+    ofs.update_sizes();
+    Ok(create_analysis_report(ofs, &image.image, source))
+}
+
+// Archives and directories can hold several images, each one is cached on its own
+fn analyze_local_image(
+    image: &str,
+    workspace: &Workspace,
+    selection: &Selection,
+    platforms: &Platforms,
+    options: &Options,
+) -> Result<()> {
+    let fingerprint = resolve_digest(image, None)?;
+    let unpacked = unpack_archive(image)?;
+    let store = open_store(unpacked_path(image, &unpacked)?)?;
+    let manifests = Manifest::list(store.as_ref())?;
+    let manifests = filter_platforms(manifests, platforms)
+        .and_then(|m| select_manifests(m, selection))
+        .map_err(|e| Error::ImageNotFound(format!("{} in {}", e, image)))?;
+
+    let mut parsed = HashMap::new();
+    let mut reports = vec![];
+    for manifest in manifests.iter() {
+        let name = manifest.name();
+        let key = format!("{}:{}", fingerprint, manifest.config_digest());
+        let digest = sha256_digest(key.as_bytes());
+        trace!("Image {} in {} resolved to {}", name, image, digest);
+
+        let label = format!("{} ({})", image, name);
+        let entry = cache_entry(workspace, &label, &digest, options.hash)?;
+        let report = get_report(&entry, image, None, |ofs| {
+            read_image(ofs, store.as_ref(), manifest, &mut parsed, options.hash)
+        })?;
+
+        if let Platforms::All = platforms {
+            let label = match &manifest.platform {
+                Some(platform) => platform.to_string(),
+                None => name,
+            };
+            reports.push((label, report));
+            continue;
+        }
+
+        if manifests.len() > 1 {
+            println!();
+            println!("{}", bold(&format!("Image: {}", name)));
+        }
+        show_report(&report, options)?;
+    }
+
+    if !reports.is_empty() {
+        show_comparison(&reports);
+    }
+    Ok(())
+}
+
+pub fn list_images(image: &str) -> Result<()> {
+    if !Path::new(image).exists() {
+        return Err(Error::ImageNotFound(format!(
+            "{} is not an image archive or directory",
+            image
+        )));
+    }
+    let unpacked = unpack_archive(image)?;
+    let store = open_store(unpacked_path(image, &unpacked)?)?;
+    let manifests = Manifest::list(store.as_ref())?;
+
+    println!(
+        "{:<19}  {:>6}  {:<16}  Tags",
+        "Digest", "Layers", "Platform"
+    );
+    for m in manifests.iter() {
+        let short: String = m.config_digest().chars().take(19).collect();
+        let platform = m
+            .platform
+            .as_ref()
+            .map(|p| p.to_string())
+            .unwrap_or_default();
+        let tags = m.repo_tags.as_deref().unwrap_or(&[]).join(", ");
+        println!(
+            "{:<19}  {:>6}  {:<16}  {}",
+            short,
+            m.layers.len(),
+            platform,
+            tags
+        );
+    }
+    Ok(())
+}
+
+// Images without platform information, like the ones from docker save, are always kept
+fn filter_platforms(
+    manifests: Vec<Manifest>,
+    platforms: &Platforms,
+) -> std::result::Result<Vec<Manifest>, String> {
+    let manifests: Vec<Manifest> = manifests
+        .into_iter()
+        .filter(|m| !m.platform.as_ref().is_some_and(|p| p.is_unknown()))
+        .collect();
+    let for_platform = |platform: &Platform| -> Vec<Manifest> {
+        manifests
+            .iter()
+            .filter(|m| m.platform.as_ref().is_none_or(|p| p.matches(platform)))
+            .cloned()
+            .collect()
+    };
+
+    match platforms {
+        Platforms::All => Ok(manifests),
+        Platforms::Only(platform) => {
+            let selected = for_platform(platform);
+            if selected.is_empty() {
+                return Err(format!("No image for {}", platform));
+            }
+            Ok(selected)
+        }
+        Platforms::Default => {
+            let selected = for_platform(&Platform::host());
+            if selected.is_empty() {
+                return Ok(manifests);
+            }
+            Ok(selected)
+        }
     }
+}
 
-    if pkgs {
+// A multi-arch image has one manifest per platform, all with the same name
+fn select_manifests(
+    manifests: Vec<Manifest>,
+    selection: &Selection,
+) -> std::result::Result<Vec<Manifest>, String> {
+    if manifests.is_empty() {
+        return Err("No image found".to_string());
+    }
+
+    match selection {
+        Selection::All => Ok(manifests),
+        Selection::First => {
+            let name = manifests[0].name();
+            let (first, others): (Vec<Manifest>, Vec<Manifest>) =
+                manifests.into_iter().partition(|m| m.name() == name);
+            if !others.is_empty() {
+                info!(
+                    "Found {} more images, analyzing {}. Use --select or --all for the others",
+                    others.len(),
+                    name
+                );
+            }
+            Ok(first)
+        }
+        Selection::Image(selector) => {
+            let selected: Vec<Manifest> = manifests
+                .into_iter()
+                .filter(|m| m.matches(selector))
+                .collect();
+            if selected.is_empty() {
+                return Err(format!("No image matching {}", selector));
+            }
+            Ok(selected)
+        }
+    }
+}
+
+// Reports with content hashes are cached apart from the ones without
+fn cache_entry(workspace: &Workspace, image: &str, digest: &str, hash: bool) -> Result<Entry> {
+    let digest = if hash {
+        sha256_digest(format!("{}:content", digest).as_bytes())
+    } else {
+        digest.to_string()
+    };
+    let entry = workspace
+        .entry(&digest)
+        .context("Failed to create cache entry")?;
+    entry.touch(image, &digest).map_err(|e| {
+        Error::CacheInvalid(format!("Failed to update cache entry {}: {}", digest, e))
+    })?;
+    Ok(entry)
+}
+
+fn get_report<F: FnOnce(&mut OverlayFs) -> Result<()>>(
+    entry: &Entry,
+    image: &str,
+    source: Option<&dyn ImageSource>,
+    read: F,
+) -> Result<AnalysisReport> {
+    let digest = entry.digest();
+    if let Some(report) = read_report(entry, &digest)? {
+        return Ok(report);
+    }
+
+    let overlayfs = create_ofs(read)?;
+    let report = create_analysis_report(overlayfs, image, source);
+    // A cache entry which can not be replaced would fail the same way on every run
+    cache::save(&entry.report_file(), &digest, &report)
+        .map_err(|e| Error::CacheInvalid(e.to_string()))?;
+    entry.cleanup();
+    Ok(report)
+}
+
+fn show_report(report: &AnalysisReport, options: &Options) -> Result<()> {
+    match options.layers {
+        Some(LayerView::All) => {
+            show_layers(&report.ofs);
+            return Ok(());
+        }
+        Some(LayerView::Changes(index)) => return show_layer_changes(&report.ofs, index),
+        None => {}
+    }
+
+    if options.interactive {
+        return tui::run(report);
+    }
+
+    if let Some(tree) = &options.tree {
+        return report.ofs.show_as_tree(&tree.path, &tree.options);
+    }
+
+    if options.pkgs {
         report.show_packages();
     } else {
         report.show_report();
     }
+    Ok(())
 }
 
-fn read_report(image: &str) -> Option<AnalysisReport> {
-    let image_json = format!("{}_report.json", image);
-    if Path::new(&image_json).exists() {
-        trace!("Loaded analysis report from cache: {}_report", image);
-        return Some(AnalysisReport::create_report_from_json(image));
+// None means the image is pulled from its registry
+fn image_source(name: &str) -> Option<Box<dyn ImageSource>> {
+    match name {
+        REGISTRY_SOURCE => None,
+        source::AUTO => {
+            let detected = source::detect();
+            if detected.is_none() {
+                info!("No container runtime found, pulling from the registry");
+            }
+            detected
+        }
+        _ => source::for_name(name),
     }
-    None
 }
 
-fn create_ofs(image: &str) -> OverlayFs {
+// Cache entries are keyed by what the image currently points to, so a tag
+// that moved to a new image is analyzed again.
+fn resolve_digest(image: &str, source: Option<&dyn ImageSource>) -> Result<String> {
+    let path = Path::new(image);
+    if path.is_file() {
+        // Hashing a multi GB archive takes longer than reading it, fingerprint it instead
+        let meta = fs::metadata(path).context(image)?;
+        let modified = meta
+            .modified()
+            .ok()
+            .and_then(|m| m.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let canonical = fs::canonicalize(path).context(image)?;
+        let fingerprint = format!("{}:{}:{}", canonical.display(), meta.len(), modified);
+        Ok(sha256_digest(fingerprint.as_bytes()))
+    } else if path.is_dir() {
+        let manifest = fs::read(path.join("manifest.json"))
+            .or_else(|_| fs::read(path.join(INDEX_FILE)))
+            .context(format!("{} is not an image directory", image))?;
+        Ok(sha256_digest(&manifest))
+    } else if let Some(source) = source {
+        Ok(source.inspect(image)?.id)
+    } else {
+        registry::resolve_digest(image)
+    }
+}
+
+// A cache written by another version of cake or for another image is replaced
+fn read_report(entry: &Entry, digest: &str) -> Result<Option<AnalysisReport>> {
+    let report_file = entry.report_file();
+    match cache::load(&report_file, digest) {
+        Ok(Some(report)) => {
+            trace!("Loaded analysis report from cache: {:?}", report_file);
+            Ok(Some(report))
+        }
+        Ok(None) => Ok(None),
+        Err(Error::CacheInvalid(reason)) => {
+            info!("{}, analyzing the image again", reason);
+            Ok(None)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+fn create_ofs<F: FnOnce(&mut OverlayFs) -> Result<()>>(read: F) -> Result<OverlayFs> {
     let mut overlayfs = OverlayFs::new();
-    let image_json = format!("{}.json", image);
+    read(&mut overlayfs)?;
+    overlayfs.update_sizes();
+    Ok(overlayfs)
+}
+
+// Compressed archives are read from an unpacked copy, removed when it is dropped
+fn unpack_archive(image: &str) -> Result<Option<NamedTempFile>> {
+    if !is_compressed_file(Path::new(image)) {
+        return Ok(None);
+    }
+    archive::unpack(image)
+        .map(Some)
+        .map_err(|e| Error::corrupt(image, e))
+}
+
+fn unpacked_path<'a>(image: &'a str, unpacked: &'a Option<NamedTempFile>) -> Result<&'a str> {
+    match unpacked {
+        Some(file) => file
+            .path()
+            .to_str()
+            .ok_or_else(|| Error::Invalid(format!("Invalid temporary path {:?}", file.path()))),
+        None => Ok(image),
+    }
+}
+
+// The cache lives below $XDG_CACHE_HOME or $HOME, which need not be UTF-8
+fn cache_path(path: &Path) -> Result<&str> {
+    path.to_str()
+        .ok_or_else(|| Error::Invalid(format!("Invalid cache path {:?}", path)))
+}
+
+fn open_store(image: &str) -> Result<Box<dyn ImageStore + '_>> {
+    if Path::new(image).is_dir() {
+        return Ok(Box::new(DirStore::new(image)));
+    }
 
-    if Path::new(&image_json).exists() {
-        overlayfs = OverlayFs::create_fs_from_json(image);
-        trace!("Loaded from cache: {}", image);
+    // A `docker save` or OCI archive produced elsewhere, no daemon needed
+    match ImageArchive::open(image) {
+        Ok(archive) => Ok(Box::new(archive)),
+        Err(e) => Err(Error::corrupt(image, e)),
+    }
+}
+
+fn read_remote_image(
+    overlayfs: &mut OverlayFs,
+    image: &str,
+    source: Option<&dyn ImageSource>,
+    platform: Option<&Platform>,
+    entry: &Entry,
+    hash: bool,
+) -> Result<()> {
+    if let Some(source) = source {
+        let image_tar = entry.image_tar();
+        let image_tar = cache_path(&image_tar)?;
+        fetch(image, source, platform, image_tar)?;
+
+        let store = open_store(image_tar)?;
+        let manifest = Manifest::for_store(store.as_ref())?;
+        read_image(
+            overlayfs,
+            store.as_ref(),
+            &manifest,
+            &mut HashMap::new(),
+            hash,
+        )
     } else {
-        let image_dir = format!("{}/", image);
-        if !Path::new(&image_dir).exists() {
-            fetch(image);
-            extract_image_tar(image);
-        }
+        // Layers are read while they are downloaded into an OCI layout
+        let image_dir = entry.image_dir();
+        registry::pull(image, platform, cache_path(&image_dir)?, overlayfs, hash)
+    }
+}
 
-        let manifest = Manifest::for_image_path(image).unwrap();
-        for (i, layer_path) in manifest.layers.iter().enumerate() {
-            let path = format!("{}/{}", image, layer_path);
-            trace!("path : {}", path);
-            let layer_id = format!("layer{}", i);
-            read_tar_layer(&mut overlayfs, &path, &layer_id);
+// Layers are read straight out of the archive, nothing is extracted to disk.
+// Every layer is checked against the digests of the manifest and image config.
+fn read_image(
+    overlayfs: &mut OverlayFs,
+    store: &dyn ImageStore,
+    manifest: &Manifest,
+    parsed: &mut ParsedLayers,
+    hash: bool,
+) -> Result<()> {
+    let config = read_config(store, manifest)?;
+    let diff_ids = config.as_ref().map(|c| &c.rootfs.diff_ids);
+    if let Some(diff_ids) = diff_ids {
+        if diff_ids.len() != manifest.layers.len() {
+            return Err(Error::CorruptArchive(format!(
+                "Image config of {} lists {} layers, the manifest {}",
+                manifest.name(),
+                diff_ids.len(),
+                manifest.layers.len()
+            )));
+        }
+    }
 
-            overlayfs.add_layer(Layer::new(layer_id, path));
+    for (i, layer_path) in manifest.layers.iter().enumerate() {
+        let layer = store
+            .layer(i, layer_path)
+            .map_err(|e| Error::corrupt(layer_path, e))?;
+        trace!("path : {} {:?}", layer.path, layer.entry);
+        let key = (layer.path.clone(), layer.entry.map(|e| e.offset));
+        if !parsed.contains_key(&key) {
+            parsed.insert(key.clone(), parse_tar_layer(&layer, hash)?);
         }
+        let parsed_layer = &parsed[&key];
+
+        let blob = blob_digest(layer_path);
+        let diff_id = diff_ids.map(|d| d[i].as_str());
+        parsed_layer
+            .digests
+            .verify(layer_path, blob.as_deref(), diff_id)?;
+        apply_layer(overlayfs, &parsed_layer.entries, &layer.layer_id)?;
 
-        overlayfs.update_sizes();
-        overlayfs.save_tree_to_json(image);
+        overlayfs.add_layer(layer);
     }
-    overlayfs
+
+    if let Some(config) = &config {
+        overlayfs.set_history(&config.history);
+    }
+    Ok(())
 }
 
-fn fetch(id: &str) {
-    info!("Fetching image docker://{}", id);
-    info!("Can take a while for larger images");
+// A config which can not be parsed only loses the build history,
+// one which does not match its digest fails the analysis
+fn read_config(store: &dyn ImageStore, manifest: &Manifest) -> Result<Option<ImageConfig>> {
+    let content = match store.read_to_string(&manifest.config) {
+        Ok(content) => content,
+        Err(e) => {
+            warn!("No build history for {}: {}", manifest.name(), e);
+            return Ok(None);
+        }
+    };
+    // Hand made archives may use any name for the config
+    let digest = manifest.config_digest();
+    if is_sha256_digest(&digest) {
+        verify_digest("image config", &sha256_digest(content.as_bytes()), &digest)?;
+    }
 
-    let result = docker::inspect(id);
+    match ImageConfig::from_slice(content.as_bytes()) {
+        Ok(config) => Ok(Some(config)),
+        Err(e) => {
+            warn!("No build history for {}: {}", manifest.name(), e);
+            Ok(None)
+        }
+    }
+}
 
-    let image_inspects: Vec<ImageInspect> = serde_json::from_str(&result).unwrap();
-    trace!("After unmarshal: {:?}", image_inspects.get(0).unwrap());
+fn fetch(
+    id: &str,
+    source: &dyn ImageSource,
+    platform: Option<&Platform>,
+    output: &str,
+) -> Result<()> {
+    info!("Fetching image {}://{}", source.name(), id);
+    info!("Can take a while for larger images");
 
-    docker::save(id);
+    source.save(id, output, platform)?;
     trace!("Image saved");
+    Ok(())
 }
 
-fn create_analysis_report(ofs: OverlayFs, image: &str) -> AnalysisReport {
+fn create_analysis_report(
+    ofs: OverlayFs,
+    image: &str,
+    source: Option<&dyn ImageSource>,
+) -> AnalysisReport {
     let alpine_pkg = AlpinePackageManager::new(&ofs);
-    let rpm_pkg = RPMPackageManager::new(&ofs, image);
+    // Local archives and directories can not be run, skip the runtime based lookup
+    let runner = if Path::new(image).exists() {
+        None
+    } else {
+        source
+    };
+    let rpm_pkg = RPMPackageManager::new(&ofs, image, runner);
     let deb_pkg = DebianPackageManager::new(&ofs);
     let archive_mgr = ArchiveManager::new(&ofs);
 
@@ -121,7 +741,331 @@ fn create_analysis_report(ofs: OverlayFs, image: &str) -> AnalysisReport {
         },
     ];
 
-    let report = AnalysisReport::create_analysis_report(ofs, image, pkg_managers);
-    report.save_report_as_json();
-    report
+    AnalysisReport::create_analysis_report(ofs, image, pkg_managers)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::analyze::{
+        cache_path, create_analysis_report, create_container_report, get_report, open_store,
+        read_image, read_rootfs, select_manifests, unpack_archive, unpacked_path, Selection,
+    };
+    use crate::docker::container::parse_diff;
+    use crate::error::{Error, EXIT_CACHE_INVALID};
+    use crate::image::image_tar::{apply_layer, parse_tar_layer};
+    use crate::image::manifest::Manifest;
+    use crate::image::rootfs::is_rootfs;
+    use crate::ofs::layer::Layer;
+    use crate::ofs::ofs::OverlayFs;
+    use crate::workspace::Workspace;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::collections::HashMap;
+    use std::path::{Path, PathBuf};
+    use tar::{Builder, Header};
+
+    const LAYER1: &str =
+        "blobs/sha256/9d88a2580b57ae893043614360747707059c6ec8278753f2177bb9616bc5a202";
+    const LAYER2: &str =
+        "blobs/sha256/0282d12b990560fa371a1dadf00618d3a070816a5a6bb5497048e901d76d84bb";
+
+    // Like `docker save app`, of the image in test_files/oci
+    fn oci_archive(dir: &Path) -> PathBuf {
+        let archive = dir.join("app.tar");
+        let mut builder = Builder::new(std::fs::File::create(&archive).unwrap());
+        builder.append_dir_all(".", "test_files/oci").unwrap();
+        builder.finish().unwrap();
+        archive
+    }
+
+    #[test]
+    fn analyzes_local_image_archive() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive = oci_archive(dir.path());
+
+        let workspace = Workspace::at(dir.path().join("cache"));
+        let entry = workspace.entry("sha256:app").unwrap();
+        let image = archive.to_str().unwrap();
+        let store = open_store(image).unwrap();
+        let manifest = Manifest::for_store(store.as_ref()).unwrap();
+        let report = get_report(&entry, image, None, |ofs| {
+            read_image(ofs, store.as_ref(), &manifest, &mut HashMap::new(), false)
+        })
+        .unwrap();
+        let ofs = &report.ofs;
+        assert_eq!(ofs.get_file("/bin/busybox").unwrap().versions.len(), 1);
+        assert!(ofs.get_layer("layer1").unwrap().entry.is_some());
+        assert!(!entry.image_dir().exists());
+    }
+
+    // The second run reads the cache, a cache of an older format is replaced
+    #[test]
+    fn reuses_and_replaces_cached_reports() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive = oci_archive(dir.path());
+
+        let workspace = Workspace::at(dir.path().join("cache"));
+        let entry = workspace.entry("sha256:app").unwrap();
+        entry.touch("app", "sha256:app").unwrap();
+        let image = archive.to_str().unwrap();
+        let store = open_store(image).unwrap();
+        let manifest = Manifest::for_store(store.as_ref()).unwrap();
+        let report = get_report(&entry, image, None, |ofs| {
+            read_image(ofs, store.as_ref(), &manifest, &mut HashMap::new(), false)
+        })
+        .unwrap();
+        assert!(entry.report_file().exists());
+
+        let cached = get_report(&entry, image, None, |_| unreachable!()).unwrap();
+        assert_eq!(cached.total_space, report.total_space);
+
+        // The version follows the magic, no cake wrote version 0
+        let mut content = std::fs::read(entry.report_file()).unwrap();
+        content[4] = 0;
+        std::fs::write(entry.report_file(), &content).unwrap();
+        let rebuilt = get_report(&entry, image, None, |ofs| {
+            read_image(ofs, store.as_ref(), &manifest, &mut HashMap::new(), false)
+        })
+        .unwrap();
+        assert_eq!(rebuilt.total_space, report.total_space);
+    }
+
+    #[test]
+    fn refuses_cache_paths_which_are_not_utf8() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let path = Path::new(OsStr::from_bytes(b"/home/caf\xe9/.cache/cake"));
+        assert!(matches!(cache_path(path), Err(Error::Invalid(_))));
+        assert_eq!(cache_path(Path::new("/tmp/cake")).unwrap(), "/tmp/cake");
+    }
+
+    #[test]
+    fn fails_when_the_cached_report_can_not_be_replaced() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive = oci_archive(dir.path());
+
+        let workspace = Workspace::at(dir.path().join("cache"));
+        let entry = workspace.entry("sha256:app").unwrap();
+        std::fs::create_dir(entry.report_file()).unwrap();
+        let image = archive.to_str().unwrap();
+        let store = open_store(image).unwrap();
+        let manifest = Manifest::for_store(store.as_ref()).unwrap();
+        let error = match get_report(&entry, image, None, |ofs| {
+            read_image(ofs, store.as_ref(), &manifest, &mut HashMap::new(), false)
+        }) {
+            Ok(_) => panic!("The report was written to a directory"),
+            Err(e) => e,
+        };
+        assert!(matches!(error, Error::CacheInvalid(_)));
+        assert_eq!(error.exit_code(), EXIT_CACHE_INVALID);
+        assert!(error
+            .to_string()
+            .ends_with("remove it with `cake cache clear`"));
+    }
+
+    // The CMD step between the two layers did not write one
+    #[test]
+    fn maps_layers_to_build_history() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive = oci_archive(dir.path());
+        let store = open_store(archive.to_str().unwrap()).unwrap();
+        let manifest = Manifest::for_store(store.as_ref()).unwrap();
+
+        let mut ofs = OverlayFs::new();
+        read_image(
+            &mut ofs,
+            store.as_ref(),
+            &manifest,
+            &mut HashMap::new(),
+            false,
+        )
+        .unwrap();
+        let steps: Vec<&str> = ofs
+            .get_layers()
+            .iter()
+            .map(|l| l.layer_id.as_str())
+            .collect();
+        assert_eq!(steps, vec!["layer0", "empty1", "layer1"]);
+        assert!(ofs.get_layer("empty1").unwrap().empty_layer);
+        let created_by = ofs.get_layer("empty1").unwrap().created_by.as_deref();
+        assert!(created_by.unwrap().contains("CMD"));
+    }
+
+    // Like `docker save base app`, both images share the first layer
+    #[test]
+    fn analyzes_all_images_in_archive() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive = dir.path().join("images.tar");
+        let manifest = format!(
+            r#"[{{"Config":"base.json","RepoTags":["base:1"],"Layers":["{}"]}},{{"Config":"app.json","RepoTags":["app:latest"],"Layers":["{}","{}"]}}]"#,
+            LAYER1, LAYER1, LAYER2
+        );
+
+        let mut builder = Builder::new(std::fs::File::create(&archive).unwrap());
+        builder
+            .append_dir_all("blobs", "test_files/oci/blobs")
+            .unwrap();
+        let mut header = Header::new_gnu();
+        header.set_size(manifest.len() as u64);
+        header.set_mode(0o644);
+        builder
+            .append_data(&mut header, "manifest.json", manifest.as_bytes())
+            .unwrap();
+        builder.finish().unwrap();
+
+        let store = open_store(archive.to_str().unwrap()).unwrap();
+        let manifests = Manifest::list(store.as_ref()).unwrap();
+        let selected = select_manifests(manifests.clone(), &Selection::Image("app")).unwrap();
+        assert_eq!(selected[0].config, "app.json");
+        assert!(select_manifests(manifests.clone(), &Selection::Image("web")).is_err());
+
+        let mut parsed = HashMap::new();
+        let mut images = vec![];
+        for m in select_manifests(manifests, &Selection::All).unwrap().iter() {
+            let mut ofs = OverlayFs::new();
+            read_image(&mut ofs, store.as_ref(), m, &mut parsed, false).unwrap();
+            images.push(ofs);
+        }
+        assert_eq!(parsed.len(), 2);
+        assert!(images[0]
+            .get_file("/bin/busybox")
+            .unwrap()
+            .versions
+            .is_empty());
+        assert_eq!(
+            images[1].get_file("/bin/busybox").unwrap().versions.len(),
+            1
+        );
+    }
+
+    const APK_MUSL: &str = "C:Q1\nP:musl\nV:1.2.2-r0\nI:600\n\n";
+    const APK_CURL: &str = "C:Q2\nP:curl\nV:7.79.1-r0\nI:300\n\n";
+
+    fn write_tar(path: &Path, files: &[(&str, &str)]) {
+        let mut builder = Builder::new(std::fs::File::create(path).unwrap());
+        for (name, content) in files {
+            let mut header = Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            builder
+                .append_data(&mut header, name, content.as_bytes())
+                .unwrap();
+        }
+        builder.finish().unwrap();
+    }
+
+    fn apk_packages(report: &crate::analysis::report::AnalysisReport) -> Vec<&str> {
+        let apk = report.managers.iter().find(|m| m.name == "alpine - apk");
+        apk.unwrap()
+            .packages
+            .iter()
+            .map(|p| p.name.as_str())
+            .collect()
+    }
+
+    // An alpine image whose layers were removed, like the cache does once the report is written
+    fn alpine_image(dir: &Path) -> crate::analysis::report::AnalysisReport {
+        let layer_tar = dir.join("layer.tar");
+        write_tar(
+            &layer_tar,
+            &[
+                ("bin/busybox", "busybox"),
+                ("lib/apk/db/installed", APK_MUSL),
+            ],
+        );
+        let layer = Layer::new(0, layer_tar.to_str().unwrap().to_string());
+        let mut ofs = OverlayFs::new();
+        let entries = parse_tar_layer(&layer, false).unwrap().entries;
+        apply_layer(&mut ofs, &entries, "layer0").unwrap();
+        ofs.add_layer(layer);
+        ofs.update_sizes();
+        let image = create_analysis_report(ofs, "alpine", None);
+        assert_eq!(apk_packages(&image), vec!["musl"]);
+        std::fs::remove_file(&layer_tar).unwrap();
+        image
+    }
+
+    #[test]
+    fn lists_packages_of_containers_after_the_image_is_removed() {
+        let dir = tempfile::tempdir().unwrap();
+        let export = dir.path().join("export.tar");
+        let export_path = export.to_str().unwrap();
+
+        // The container did not touch the database
+        write_tar(
+            &export,
+            &[
+                ("bin/busybox", "busybox"),
+                ("lib/apk/db/installed", APK_MUSL),
+                ("tmp/app.log", "log"),
+            ],
+        );
+        let changes = parse_diff("C /tmp\nA /tmp/app.log\n").unwrap();
+        let image = alpine_image(dir.path());
+        let report =
+            create_container_report(image, export_path, "app", &changes, None, false).unwrap();
+        assert_eq!(apk_packages(&report), vec!["musl"]);
+
+        // The container ran `apk add curl`
+        let installed = format!("{}{}", APK_MUSL, APK_CURL);
+        write_tar(
+            &export,
+            &[
+                ("bin/busybox", "busybox"),
+                ("lib/apk/db/installed", &installed),
+                ("usr/bin/curl", "curl"),
+            ],
+        );
+        let changes = parse_diff("C /lib/apk/db/installed\nA /usr/bin/curl\n").unwrap();
+        let image = alpine_image(dir.path());
+        let report =
+            create_container_report(image, export_path, "app", &changes, None, false).unwrap();
+        assert_eq!(apk_packages(&report), vec!["musl", "curl"]);
+    }
+
+    #[test]
+    fn analyzes_empty_root_filesystem() {
+        let dir = tempfile::tempdir().unwrap();
+        let image = dir.path().to_str().unwrap();
+        let mut ofs = OverlayFs::new();
+        read_rootfs(&mut ofs, image, false).unwrap();
+        ofs.update_sizes();
+
+        let report = create_analysis_report(ofs, image, None);
+        assert_eq!(report.total_space, 0);
+        assert_eq!(report.wasted_space, 0);
+        assert_eq!(report.score, 100);
+    }
+
+    // Like `docker save app | gzip`
+    #[test]
+    fn analyzes_compressed_image_archive() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive = dir.path().join("oci.tar.gz");
+        let gzip = GzEncoder::new(
+            std::fs::File::create(&archive).unwrap(),
+            Compression::default(),
+        );
+        let mut builder = Builder::new(gzip);
+        builder.append_dir_all(".", "test_files/oci").unwrap();
+        builder.into_inner().unwrap().finish().unwrap();
+        assert!(!is_rootfs(&archive));
+
+        let image = archive.to_str().unwrap();
+        let unpacked = unpack_archive(image).unwrap();
+        assert!(unpacked.is_some());
+        let store = open_store(unpacked_path(image, &unpacked).unwrap()).unwrap();
+        let manifest = Manifest::for_store(store.as_ref()).unwrap();
+        let mut ofs = OverlayFs::new();
+        read_image(
+            &mut ofs,
+            store.as_ref(),
+            &manifest,
+            &mut HashMap::new(),
+            false,
+        )
+        .unwrap();
+        assert_eq!(ofs.get_file("/bin/busybox").unwrap().versions.len(), 1);
+    }
 }
@@ -0,0 +1,120 @@
+// Cached analyses are CBOR behind a header: the magic, the version of the format
+// and the digest of the image. A file written by another version of cake or for
+// another image is never read, the image is analyzed again instead.
+use crate::error::{Context, Error, Result};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::convert::TryInto;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::Path;
+
+const MAGIC: &[u8; 4] = b"CAKE";
+// Bump on any change to what is cached
+pub const VERSION: u32 = 1;
+
+fn write_error(path: &Path, e: ciborium::ser::Error<io::Error>) -> Error {
+    match e {
+        ciborium::ser::Error::Io(source) => Error::Io {
+            context: path.display().to_string(),
+            source,
+        },
+        ciborium::ser::Error::Value(message) => {
+            Error::Invalid(format!("Failed to write {}: {}", path.display(), message))
+        }
+    }
+}
+
+// Written next to the target and renamed, an interrupted run leaves no half file
+pub fn save<T: Serialize>(path: &Path, digest: &str, value: &T) -> Result<()> {
+    let temporary = path.with_extension("tmp");
+    let file = File::create(&temporary).context(temporary.display())?;
+    let mut writer = BufWriter::new(file);
+    writer.write_all(MAGIC).context(temporary.display())?;
+    writer
+        .write_all(&VERSION.to_le_bytes())
+        .context(temporary.display())?;
+
+    ciborium::into_writer(digest, &mut writer).map_err(|e| write_error(&temporary, e))?;
+    ciborium::into_writer(value, &mut writer).map_err(|e| write_error(&temporary, e))?;
+    writer.flush().context(temporary.display())?;
+    drop(writer);
+    fs::rename(&temporary, path).context(path.display())
+}
+
+// None when nothing is cached, CacheInvalid when the file can not be used
+pub fn load<T: DeserializeOwned>(path: &Path, digest: &str) -> Result<Option<T>> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e).context(path.display()),
+    };
+    let invalid =
+        |reason: String| Error::CacheInvalid(format!("Cached {} {}", path.display(), reason));
+
+    let mut reader = BufReader::new(file);
+    let mut header = [0; 8];
+    if reader.read_exact(&mut header).is_err() || &header[..4] != MAGIC {
+        return Err(invalid("is not a cake cache".to_string()));
+    }
+    let version = u32::from_le_bytes(header[4..].try_into().unwrap());
+    if version != VERSION {
+        return Err(invalid(format!(
+            "has format version {}, expected {}",
+            version, VERSION
+        )));
+    }
+
+    let cached: String =
+        ciborium::from_reader(&mut reader).map_err(|e| invalid(format!("is invalid: {}", e)))?;
+    if cached != digest {
+        return Err(invalid(format!("belongs to {}, not {}", cached, digest)));
+    }
+    ciborium::from_reader(&mut reader)
+        .map(Some)
+        .map_err(|e| invalid(format!("is invalid: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cache::{load, save, VERSION};
+    use crate::error::Error;
+    use std::collections::HashMap;
+    use std::fs;
+
+    #[test]
+    fn loads_only_matching_version_and_digest() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("report.cbor");
+        assert!(load::<HashMap<String, u64>>(&path, "sha256:app")
+            .unwrap()
+            .is_none());
+
+        let mut sizes = HashMap::new();
+        sizes.insert("layer0".to_string(), 300_u64);
+        save(&path, "sha256:app", &sizes).unwrap();
+        let loaded: HashMap<String, u64> = load(&path, "sha256:app").unwrap().unwrap();
+        assert_eq!(loaded, sizes);
+        assert!(!path.with_extension("tmp").exists());
+
+        // The tag moved to another image
+        assert!(matches!(
+            load::<HashMap<String, u64>>(&path, "sha256:new"),
+            Err(Error::CacheInvalid(m)) if m.ends_with("belongs to sha256:app, not sha256:new")
+        ));
+
+        let mut content = fs::read(&path).unwrap();
+        content[4..8].copy_from_slice(&(VERSION + 1).to_le_bytes());
+        fs::write(&path, &content).unwrap();
+        assert!(matches!(
+            load::<HashMap<String, u64>>(&path, "sha256:app"),
+            Err(Error::CacheInvalid(_))
+        ));
+
+        fs::write(&path, r#"{"score":100}"#).unwrap();
+        assert!(matches!(
+            load::<HashMap<String, u64>>(&path, "sha256:app"),
+            Err(Error::CacheInvalid(m)) if m.ends_with("is not a cake cache")
+        ));
+    }
+}
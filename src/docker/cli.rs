@@ -1,28 +1,154 @@
+use crate::error::{Context, Error, Result};
 use log::trace;
-use std::io::{BufReader, Read};
-use std::process::{Child, Command, Stdio};
+use std::io::Read;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
 
-pub(crate) fn docker(args: Vec<&str>) -> Result<String, String> {
-    trace!("Calling docker-cli: docker {:?}", &args[0..2]);
+// Inspecting an image should be quick, a daemon which does not answer is stuck
+pub(crate) const QUERY_TIMEOUT: Option<Duration> = Some(Duration::from_secs(30));
+// Listing the packages of an image starts a container
+pub(crate) const RUN_TIMEOUT: Option<Duration> = Some(Duration::from_secs(300));
+// How often a command with a timeout is checked for completion
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
 
-    let mut child: Child = Command::new("docker")
-        .args(args)
+// Output is read on its own threads, a full stderr pipe would block the child
+fn read_pipe<R: Read + Send + 'static>(mut pipe: R) -> JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut output = vec![];
+        let _ = pipe.read_to_end(&mut output);
+        output
+    })
+}
+
+fn wait(child: &mut Child, binary: &str, timeout: Option<Duration>) -> Result<ExitStatus> {
+    let timeout = match timeout {
+        Some(timeout) => timeout,
+        None => {
+            return child
+                .wait()
+                .context(format!("Failed to wait for {}", binary))
+        }
+    };
+
+    let start = Instant::now();
+    loop {
+        if let Some(status) = child
+            .try_wait()
+            .context(format!("Failed to wait for {}", binary))?
+        {
+            return Ok(status);
+        }
+        if start.elapsed() >= timeout {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(Error::Command(format!(
+                "{} did not finish within {:?}",
+                binary, timeout
+            )));
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+// Commands which name an image, "not found" from any other one is about something else
+const IMAGE_COMMANDS: [&str; 5] = ["image", "images", "inspect", "pull", "save"];
+
+// What docker, podman, nerdctl and ctr print for an image they do not have
+fn image_missing(message: &str) -> bool {
+    ["no such image", "image not known", "manifest unknown", "not found: manifest"]
+        .iter()
+        .any(|m| message.contains(m))
+        // ctr: image "docker.io/library/app:latest": not found
+        || (message.starts_with("ctr: image ") && message.ends_with(": not found"))
+}
+
+// The runtimes only report failures on stderr, tell the common ones apart
+fn command_error(binary: &str, args: &[&str], status: ExitStatus, stderr: &str) -> Error {
+    let message = stderr.trim();
+    let lower = message.to_lowercase();
+
+    if lower.contains("cannot connect") || lower.contains("daemon running") {
+        Error::DaemonUnavailable(message.to_string())
+    } else if IMAGE_COMMANDS.contains(args.first().unwrap_or(&"")) && image_missing(&lower) {
+        Error::ImageNotFound(message.to_string())
+    } else {
+        Error::Command(format!(
+            "{} {} failed ({}): {}",
+            binary,
+            args.first().unwrap_or(&""),
+            status,
+            message
+        ))
+    }
+}
+
+// Runs the runtime cli without a terminal and returns what it wrote to stdout
+pub(crate) fn exec(binary: &str, args: Vec<&str>, timeout: Option<Duration>) -> Result<String> {
+    trace!("Calling {}-cli: {} {:?}", binary, binary, args);
+
+    let mut child: Child = Command::new(binary)
+        .args(&args)
+        .stdin(Stdio::null())
         .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
         .spawn()
-        .expect("Failed to spawn child process");
+        .map_err(|e| Error::DaemonUnavailable(format!("Failed to run {}: {}", binary, e)))?;
 
-    let stdout = child.stdout.as_mut().expect("Failed to open stdout");
+    let stdout = read_pipe(child.stdout.take().unwrap());
+    let stderr = read_pipe(child.stderr.take().unwrap());
 
-    let mut reader = BufReader::new(stdout);
-    let mut input = String::new();
+    trace!("Waiting for command to finish");
+    let status = wait(&mut child, binary, timeout)?;
+    let stdout = stdout.join().unwrap_or_default();
+    let stderr = String::from_utf8_lossy(&stderr.join().unwrap_or_default()).into_owned();
 
-    let result = match reader.read_to_string(&mut input) {
-        Ok(_) => Ok(input),
-        Err(err) => Err(err.to_string()),
-    };
+    if !status.success() {
+        return Err(command_error(binary, &args, status, &stderr));
+    }
+    if !stderr.trim().is_empty() {
+        trace!("{} stderr: {}", binary, stderr.trim());
+    }
+    String::from_utf8(stdout)
+        .map_err(|e| Error::Command(format!("Invalid {} output: {}", binary, e)))
+}
 
-    trace!("Waiting for command to finish");
-    let _ = child.wait();
+#[cfg(test)]
+mod tests {
+    use crate::docker::cli::command_error;
+    use crate::error::Error;
+    use std::os::unix::process::ExitStatusExt;
+    use std::process::ExitStatus;
+
+    #[test]
+    fn tells_missing_images_from_other_failures() {
+        let status = ExitStatus::from_raw(1 << 8);
+        let error = |args: &[&str], stderr: &str| command_error("docker", args, status, stderr);
 
-    result
+        assert!(matches!(
+            error(&["image", "inspect", "web"], "Error: No such image: web"),
+            Error::ImageNotFound(_)
+        ));
+        assert!(matches!(
+            error(
+                &["pull", "web"],
+                "Error response from daemon: manifest unknown"
+            ),
+            Error::ImageNotFound(_)
+        ));
+        assert!(matches!(
+            error(
+                &["images", "export"],
+                "ctr: image \"web:latest\": not found"
+            ),
+            Error::ImageNotFound(_)
+        ));
+        assert!(matches!(
+            error(
+                &["run", "--rm", "app", "rpm"],
+                "exec: \"rpm\": executable file not found in $PATH"
+            ),
+            Error::Command(_)
+        ));
+    }
 }
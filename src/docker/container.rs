@@ -0,0 +1,125 @@
+// What a container changed on top of its image, from `docker diff` or the
+// changes endpoint of the engine API.
+use crate::error::{Error, Result};
+use serde::Deserialize;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ChangeKind {
+    Modified,
+    Added,
+    Deleted,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Change {
+    pub path: String,
+    pub kind: ChangeKind,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "PascalCase", default)]
+pub struct ContainerImage {
+    pub image: String,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "PascalCase", default)]
+pub struct ContainerInspect {
+    #[serde(rename = "Id")]
+    pub id: String,
+    pub name: String,
+    // Id of the image the container was created from
+    pub image: String,
+    // The image as it was given to `docker run`
+    pub config: ContainerImage,
+}
+
+impl ContainerInspect {
+    pub fn image_name(&self) -> &str {
+        if self.config.image.is_empty() {
+            &self.image
+        } else {
+            &self.config.image
+        }
+    }
+}
+
+// `docker diff` prints one change per line: C /etc, A /tmp/cache, D /root/.profile
+pub fn parse_diff(output: &str) -> Result<Vec<Change>> {
+    output
+        .lines()
+        .filter(|l| !l.trim().is_empty())
+        .map(|line| {
+            let (kind, path) = line
+                .split_once(' ')
+                .ok_or_else(|| Error::Invalid(format!("Invalid container change: {}", line)))?;
+            let kind = match kind {
+                "C" => ChangeKind::Modified,
+                "A" => ChangeKind::Added,
+                "D" => ChangeKind::Deleted,
+                _ => {
+                    return Err(Error::Invalid(format!(
+                        "Invalid container change: {}",
+                        line
+                    )))
+                }
+            };
+            Ok(Change {
+                path: path.to_string(),
+                kind,
+            })
+        })
+        .collect()
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ApiChange {
+    path: String,
+    kind: u8,
+}
+
+// The engine API numbers the kinds: 0 modified, 1 added, 2 deleted
+pub fn parse_changes(body: &str) -> Result<Vec<Change>> {
+    // A container without changes answers null
+    let changes: Option<Vec<ApiChange>> = serde_json::from_str(body)
+        .map_err(|e| Error::Invalid(format!("Failed to parse container changes: {}", e)))?;
+    changes
+        .unwrap_or_default()
+        .into_iter()
+        .map(|c| {
+            let kind = match c.kind {
+                0 => ChangeKind::Modified,
+                1 => ChangeKind::Added,
+                2 => ChangeKind::Deleted,
+                k => return Err(Error::Invalid(format!("Unknown change kind {}", k))),
+            };
+            Ok(Change { path: c.path, kind })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::docker::container::{parse_changes, parse_diff, Change, ChangeKind};
+
+    #[test]
+    fn parses_cli_and_api_changes() {
+        let cli = parse_diff("C /var\nA /var/log/app.log\nD /etc/motd\n").unwrap();
+        let api = parse_changes(
+            r#"[{"Path":"/var","Kind":0},{"Path":"/var/log/app.log","Kind":1},{"Path":"/etc/motd","Kind":2}]"#,
+        )
+        .unwrap();
+        assert_eq!(cli, api);
+        assert_eq!(
+            cli[1],
+            Change {
+                path: "/var/log/app.log".to_string(),
+                kind: ChangeKind::Added
+            }
+        );
+
+        assert!(parse_changes("null").unwrap().is_empty());
+        assert!(parse_diff("X /tmp").is_err());
+    }
+}
@@ -0,0 +1,94 @@
+// containerd's ctr has its own cli, images need fully qualified names.
+use super::cli;
+use super::source::{ImageSource, CTR};
+use crate::error::{Error, Result};
+use crate::image::inspect::ImageInspect;
+use crate::image::oci::Platform;
+use crate::registry::reference::{Reference, DOCKER_HUB};
+use std::process;
+
+pub struct Ctr {}
+
+fn qualified_name(image: &str) -> String {
+    let reference = Reference::parse(image);
+    let registry = if reference.registry == DOCKER_HUB {
+        "docker.io"
+    } else {
+        &reference.registry
+    };
+
+    match &reference.digest {
+        Some(digest) => format!("{}/{}@{}", registry, reference.repository, digest),
+        None => format!("{}/{}:{}", registry, reference.repository, reference.tag),
+    }
+}
+
+// Parses `ctr images ls` output: REF TYPE DIGEST SIZE PLATFORMS LABELS
+fn parse_images_ls(output: &str, name: &str) -> Option<ImageInspect> {
+    output
+        .lines()
+        .skip(1)
+        .map(|l| l.split_whitespace().collect::<Vec<&str>>())
+        .find(|columns| columns.len() > 2 && columns[0] == name)
+        .map(|columns| ImageInspect {
+            id: columns[2].to_string(),
+            repo_tags: vec![columns[0].to_string()],
+            ..Default::default()
+        })
+}
+
+impl ImageSource for Ctr {
+    fn name(&self) -> &str {
+        CTR
+    }
+
+    fn inspect(&self, image: &str) -> Result<ImageInspect> {
+        let name = qualified_name(image);
+        let filter = format!("name=={}", name);
+        let output = cli::exec(CTR, vec!["images", "ls", &filter], cli::QUERY_TIMEOUT)?;
+        parse_images_ls(&output, &name).ok_or(Error::ImageNotFound(name))
+    }
+
+    fn save(&self, image: &str, output: &str, platform: Option<&Platform>) -> Result<()> {
+        let name = qualified_name(image);
+        let platform = platform.map(|p| p.to_string());
+        let mut export = vec!["images", "export"];
+        if let Some(platform) = &platform {
+            export.extend(vec!["--platform", platform]);
+        }
+        export.extend(vec![output, &name]);
+        cli::exec(CTR, export, None).map(|_| ())
+    }
+
+    fn run(&self, image: &str, args: Vec<&str>) -> Result<String> {
+        let name = qualified_name(image);
+        let container = format!("cake-{}", process::id());
+        let mut run = vec!["run", "--rm", &name, &container];
+        run.extend(args);
+        cli::exec(CTR, run, cli::RUN_TIMEOUT)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::docker::ctr::{parse_images_ls, qualified_name};
+
+    #[test]
+    fn qualifies_image_names() {
+        assert_eq!(qualified_name("alpine:3"), "docker.io/library/alpine:3");
+        assert_eq!(qualified_name("ghcr.io/org/app"), "ghcr.io/org/app:latest");
+    }
+
+    #[test]
+    fn parses_images_ls() {
+        let output = "REF                        TYPE                                                      DIGEST                                                                  SIZE    PLATFORMS   LABELS
+docker.io/library/alpine:3 application/vnd.docker.distribution.manifest.list.v2+json sha256:69e70a79f2d41ab5d637de98c1e0b055206ba40a8145e7bddb55ccc04e13cf8f 2.7 MiB linux/amd64 -
+";
+        let inspect = parse_images_ls(output, "docker.io/library/alpine:3").unwrap();
+        assert_eq!(
+            inspect.id,
+            "sha256:69e70a79f2d41ab5d637de98c1e0b055206ba40a8145e7bddb55ccc04e13cf8f"
+        );
+        assert!(parse_images_ls(output, "docker.io/library/debian:11").is_none());
+    }
+}
@@ -0,0 +1,350 @@
+// Talks to the Docker Engine API on its unix socket, no docker binary needed.
+// https://docs.docker.com/engine/api/
+use super::container::{self, Change, ContainerInspect};
+use super::source::{check_local_platform, DockerCli, ImageSource, DOCKER, DOCKER_API};
+use crate::error::{Context, Error, Result};
+use crate::image::inspect::ImageInspect;
+use crate::image::oci::Platform;
+use log::{trace, warn};
+use serde::Deserialize;
+use std::env;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+
+const DEFAULT_SOCKET: &str = "/var/run/docker.sock";
+
+// Error responses of the engine carry a message
+#[derive(Deserialize)]
+struct ErrorResponse {
+    message: String,
+}
+
+// The socket of DOCKER_HOST, only unix sockets are supported
+pub fn socket_path(docker_host: Option<&str>) -> Option<PathBuf> {
+    match docker_host {
+        Some(host) => host.strip_prefix("unix://").map(PathBuf::from),
+        None => Some(PathBuf::from(DEFAULT_SOCKET)),
+    }
+}
+
+// The socket DOCKER_HOST points to, None when it is not a unix socket
+pub fn local_socket() -> Option<PathBuf> {
+    let host = env::var("DOCKER_HOST").ok().filter(|h| !h.is_empty());
+    socket_path(host.as_deref())
+}
+
+// Decodes a `Transfer-Encoding: chunked` body
+struct ChunkedReader<R: BufRead> {
+    reader: R,
+    remaining: u64,
+    done: bool,
+}
+
+impl<R: BufRead> ChunkedReader<R> {
+    fn new(reader: R) -> ChunkedReader<R> {
+        ChunkedReader {
+            reader,
+            remaining: 0,
+            done: false,
+        }
+    }
+
+    fn read_line(&mut self) -> io::Result<String> {
+        let mut line = String::new();
+        self.reader.read_line(&mut line)?;
+        Ok(line.trim_end().to_string())
+    }
+
+    fn next_chunk(&mut self) -> io::Result<()> {
+        let line = self.read_line()?;
+        // Chunk extensions after `;` are ignored
+        let size = line.split(';').next().unwrap_or("").trim();
+        self.remaining = u64::from_str_radix(size, 16).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Invalid chunk size: {:?}", line),
+            )
+        })?;
+
+        if self.remaining == 0 {
+            // Skip the trailers up to the empty line
+            while !self.read_line()?.is_empty() {}
+            self.done = true;
+        }
+        Ok(())
+    }
+}
+
+impl<R: BufRead> Read for ChunkedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.done || buf.is_empty() {
+            return Ok(0);
+        }
+        if self.remaining == 0 {
+            self.next_chunk()?;
+            if self.done {
+                return Ok(0);
+            }
+        }
+
+        let max = buf.len().min(self.remaining as usize);
+        let n = self.reader.read(&mut buf[..max])?;
+        if n == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        self.remaining -= n as u64;
+        if self.remaining == 0 {
+            // Every chunk ends with CRLF
+            self.read_line()?;
+        }
+        Ok(n)
+    }
+}
+
+type Body = Box<dyn Read>;
+
+fn read_body(mut body: Body) -> io::Result<String> {
+    let mut content = String::new();
+    body.read_to_string(&mut content)?;
+    Ok(content)
+}
+
+pub struct Engine {
+    socket: PathBuf,
+}
+
+impl Engine {
+    pub fn new(socket: &Path) -> Engine {
+        Engine {
+            socket: socket.to_path_buf(),
+        }
+    }
+
+    pub fn local() -> Engine {
+        let socket = local_socket().unwrap_or_else(|| {
+            warn!("Only unix sockets are supported, using {}", DEFAULT_SOCKET);
+            PathBuf::from(DEFAULT_SOCKET)
+        });
+        Engine::new(&socket)
+    }
+
+    fn unavailable(&self, e: io::Error) -> Error {
+        Error::DaemonUnavailable(format!(
+            "Failed to talk to the docker engine at {}: {}",
+            self.socket.display(),
+            e
+        ))
+    }
+
+    fn get(&self, path: &str) -> Result<Body> {
+        trace!("GET {} on {}", path, self.socket.display());
+        let mut stream = UnixStream::connect(&self.socket).map_err(|e| self.unavailable(e))?;
+        write!(
+            stream,
+            "GET {} HTTP/1.1\r\nHost: docker\r\nConnection: close\r\n\r\n",
+            path
+        )
+        .map_err(|e| self.unavailable(e))?;
+
+        let mut reader = BufReader::new(stream);
+        let (status, chunked, length) = read_head(&mut reader).map_err(|e| self.unavailable(e))?;
+        let body: Body = if chunked {
+            Box::new(ChunkedReader::new(reader))
+        } else if let Some(length) = length {
+            Box::new(reader.take(length))
+        } else {
+            Box::new(reader)
+        };
+
+        if status / 100 == 2 {
+            return Ok(body);
+        }
+        let body = read_body(body).map_err(|e| self.unavailable(e))?;
+        let message = serde_json::from_str::<ErrorResponse>(&body)
+            .map(|e| e.message)
+            .unwrap_or(body);
+        Err(match status {
+            404 => Error::ImageNotFound(message),
+            _ => Error::Command(format!("Docker engine returned {}: {}", status, message)),
+        })
+    }
+
+    fn get_string(&self, path: &str) -> Result<String> {
+        read_body(self.get(path)?).map_err(|e| self.unavailable(e))
+    }
+
+    // Streams a tar to the output file
+    fn download(&self, path: &str, output: &str) -> Result<()> {
+        let mut body = self.get(path)?;
+        let mut file = File::create(output).context(output)?;
+        io::copy(&mut body, &mut file).context(format!("Failed to save {}", output))?;
+        Ok(())
+    }
+}
+
+// Reads the status line and the headers which tell how the body is sent
+fn read_head<R: BufRead>(reader: &mut R) -> io::Result<(u16, bool, Option<u64>)> {
+    let invalid = |what: &str| io::Error::new(io::ErrorKind::InvalidData, what.to_string());
+
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let status = line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|s| s.parse().ok())
+        .ok_or_else(|| invalid(&format!("Invalid status line: {:?}", line.trim_end())))?;
+
+    let mut chunked = false;
+    let mut length = None;
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(invalid("Connection closed in the response headers"));
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            let value = value.trim();
+            if name.eq_ignore_ascii_case("transfer-encoding") {
+                chunked = value.eq_ignore_ascii_case("chunked");
+            } else if name.eq_ignore_ascii_case("content-length") {
+                length = value.parse().ok();
+            }
+        }
+    }
+    Ok((status, chunked, length))
+}
+
+impl ImageSource for Engine {
+    fn name(&self) -> &str {
+        DOCKER_API
+    }
+
+    fn inspect(&self, image: &str) -> Result<ImageInspect> {
+        let body = self.get_string(&format!("/images/{}/json", image))?;
+        serde_json::from_str(&body)
+            .map_err(|e| Error::Invalid(format!("Failed to parse engine inspect: {}", e)))
+    }
+
+    fn save(&self, image: &str, output: &str, platform: Option<&Platform>) -> Result<()> {
+        if let Some(platform) = platform {
+            check_local_platform(self.inspect(image)?, image, platform)?;
+        }
+        self.download(&format!("/images/{}/get", image), output)
+    }
+
+    // Starting containers is left to the docker cli
+    fn run(&self, image: &str, args: Vec<&str>) -> Result<String> {
+        DockerCli::new(DOCKER).run(image, args)
+    }
+
+    fn inspect_container(&self, id: &str) -> Result<ContainerInspect> {
+        let body = self.get_string(&format!("/containers/{}/json", id))?;
+        serde_json::from_str(&body)
+            .map_err(|e| Error::Invalid(format!("Failed to parse engine inspect: {}", e)))
+    }
+
+    fn container_changes(&self, id: &str) -> Result<Vec<Change>> {
+        container::parse_changes(&self.get_string(&format!("/containers/{}/changes", id))?)
+    }
+
+    fn export_container(&self, id: &str, output: &str) -> Result<()> {
+        self.download(&format!("/containers/{}/export", id), output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::docker::engine::{socket_path, ChunkedReader, Engine};
+    use crate::docker::source::ImageSource;
+    use crate::error::Error;
+    use std::fs;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::os::unix::net::UnixListener;
+    use std::path::PathBuf;
+    use std::thread;
+
+    // Answers like the engine for the `app` image, in chunks for the saved tar
+    fn start_engine(socket: PathBuf) {
+        let listener = UnixListener::bind(socket).unwrap();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut request = String::new();
+                BufReader::new(&stream).read_line(&mut request).unwrap();
+
+                let response = match request.split_whitespace().nth(1).unwrap() {
+                    "/images/app/json" => {
+                        let body = r#"{"Id":"sha256:app","RepoTags":["app:latest"],"Os":"linux","Architecture":"amd64"}"#;
+                        format!(
+                            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+                            body.len(),
+                            body
+                        )
+                    }
+                    "/images/app/get" => "HTTP/1.1 200 OK\r\nContent-Type: application/x-tar\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nimage\r\n6;ext=1\r\n layer\r\n0\r\n\r\n".to_string(),
+                    _ => {
+                        let body = r#"{"message":"No such image: web:latest"}"#;
+                        format!(
+                            "HTTP/1.1 404 Not Found\r\nContent-Length: {}\r\n\r\n{}",
+                            body.len(),
+                            body
+                        )
+                    }
+                };
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+    }
+
+    #[test]
+    fn reads_socket_from_docker_host() {
+        assert_eq!(
+            socket_path(None).unwrap(),
+            PathBuf::from("/var/run/docker.sock")
+        );
+        assert_eq!(
+            socket_path(Some("unix:///run/user/1000/docker.sock")).unwrap(),
+            PathBuf::from("/run/user/1000/docker.sock")
+        );
+        assert!(socket_path(Some("tcp://127.0.0.1:2375")).is_none());
+    }
+
+    #[test]
+    fn decodes_chunked_body() {
+        let body = "4\r\nWiki\r\n7\r\npedia i\r\n0\r\nExpires: never\r\n\r\n";
+        let mut decoded = String::new();
+        ChunkedReader::new(body.as_bytes())
+            .read_to_string(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, "Wikipedia i");
+    }
+
+    #[test]
+    fn inspects_and_saves_over_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("docker.sock");
+        start_engine(socket.clone());
+        let engine = Engine::new(&socket);
+
+        assert_eq!(engine.inspect("app").unwrap().id, "sha256:app");
+        assert!(matches!(
+            engine.inspect("web"),
+            Err(Error::ImageNotFound(m)) if m == "No such image: web:latest"
+        ));
+
+        let output = dir.path().join("app.tar");
+        engine.save("app", output.to_str().unwrap(), None).unwrap();
+        assert_eq!(fs::read_to_string(&output).unwrap(), "image layer");
+
+        let missing = Engine::new(&dir.path().join("missing.sock"));
+        assert!(matches!(
+            missing.inspect("app"),
+            Err(Error::DaemonUnavailable(_))
+        ));
+    }
+}
@@ -0,0 +1,251 @@
+use super::cli;
+use super::container::{self, Change, ContainerInspect};
+use super::ctr::Ctr;
+use super::engine::{self, Engine};
+use crate::error::{Error, Result};
+use crate::image::inspect::ImageInspect;
+use crate::image::oci::Platform;
+use log::trace;
+use std::env;
+use std::path::Path;
+
+pub const AUTO: &str = "auto";
+pub const DOCKER: &str = "docker";
+pub const DOCKER_API: &str = "docker-api";
+pub const PODMAN: &str = "podman";
+pub const NERDCTL: &str = "nerdctl";
+pub const CTR: &str = "ctr";
+
+// Order in which local runtimes are looked up when the source is `auto`
+const DETECT_ORDER: [&str; 4] = [DOCKER, PODMAN, NERDCTL, CTR];
+
+// A local container runtime which can hand us an image
+pub trait ImageSource {
+    fn name(&self) -> &str;
+    fn inspect(&self, image: &str) -> Result<ImageInspect>;
+    fn save(&self, image: &str, output: &str, platform: Option<&Platform>) -> Result<()>;
+    fn run(&self, image: &str, args: Vec<&str>) -> Result<String>;
+
+    // Only the docker compatible runtimes can hand out the changes of a container
+    fn inspect_container(&self, _id: &str) -> Result<ContainerInspect> {
+        Err(Error::Invalid(format!(
+            "{} can not analyze containers",
+            self.name()
+        )))
+    }
+    fn container_changes(&self, id: &str) -> Result<Vec<Change>> {
+        self.inspect_container(id).map(|_| vec![])
+    }
+    fn export_container(&self, id: &str, _output: &str) -> Result<()> {
+        self.inspect_container(id).map(|_| ())
+    }
+}
+
+// podman and nerdctl are drop-in replacements for the docker cli
+pub struct DockerCli {
+    name: &'static str,
+    // The executable, looked up on PATH unless a path is given
+    binary: String,
+}
+
+impl DockerCli {
+    pub fn new(name: &'static str) -> DockerCli {
+        DockerCli::at(name, name)
+    }
+
+    pub fn at(name: &'static str, binary: &str) -> DockerCli {
+        DockerCli {
+            name,
+            binary: binary.to_string(),
+        }
+    }
+}
+
+// Only one platform of an image is kept locally, `save --platform` is not
+// supported by every runtime, so check the local one instead
+pub fn check_local_platform(inspect: ImageInspect, image: &str, platform: &Platform) -> Result<()> {
+    let local = Platform {
+        os: inspect.os,
+        architecture: inspect.architecture,
+        variant: None,
+    };
+    if !local.matches(&Platform {
+        variant: None,
+        ..platform.clone()
+    }) {
+        return Err(Error::ImageNotFound(format!(
+            "{} is {} locally, pull it with --platform {} first",
+            image, local, platform
+        )));
+    }
+    Ok(())
+}
+
+impl ImageSource for DockerCli {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn inspect(&self, image: &str) -> Result<ImageInspect> {
+        let result = cli::exec(
+            &self.binary,
+            vec!["image", "inspect", image],
+            cli::QUERY_TIMEOUT,
+        )?;
+        // Errors only go to stderr, nothing on stdout means the daemon did not answer
+        if result.trim().is_empty() {
+            return Err(Error::DaemonUnavailable(format!(
+                "{} did not answer, is the daemon running?",
+                self.name
+            )));
+        }
+        let mut inspects: Vec<ImageInspect> = serde_json::from_str(&result)
+            .map_err(|e| Error::Invalid(format!("Failed to parse {} inspect: {}", self.name, e)))?;
+        if inspects.is_empty() {
+            return Err(Error::ImageNotFound(image.to_string()));
+        }
+        Ok(inspects.remove(0))
+    }
+
+    fn save(&self, image: &str, output: &str, platform: Option<&Platform>) -> Result<()> {
+        if let Some(platform) = platform {
+            check_local_platform(self.inspect(image)?, image, platform)?;
+        }
+        // Saving a large image takes as long as it takes
+        cli::exec(&self.binary, vec!["save", image, "-o", output], None).map(|_| ())
+    }
+
+    fn run(&self, image: &str, args: Vec<&str>) -> Result<String> {
+        // No -it, there is no terminal in CI and the output is captured anyway
+        let mut run = vec!["run", "--rm", image];
+        run.extend(args);
+        cli::exec(&self.binary, run, cli::RUN_TIMEOUT)
+    }
+
+    fn inspect_container(&self, id: &str) -> Result<ContainerInspect> {
+        let result = cli::exec(
+            &self.binary,
+            vec!["container", "inspect", id],
+            cli::QUERY_TIMEOUT,
+        )?;
+        let mut inspects: Vec<ContainerInspect> = serde_json::from_str(&result)
+            .map_err(|e| Error::Invalid(format!("Failed to parse {} inspect: {}", self.name, e)))?;
+        if inspects.is_empty() {
+            return Err(Error::ImageNotFound(format!("No container {}", id)));
+        }
+        Ok(inspects.remove(0))
+    }
+
+    fn container_changes(&self, id: &str) -> Result<Vec<Change>> {
+        let result = cli::exec(&self.binary, vec!["diff", id], cli::QUERY_TIMEOUT)?;
+        container::parse_diff(&result)
+    }
+
+    fn export_container(&self, id: &str, output: &str) -> Result<()> {
+        cli::exec(&self.binary, vec!["export", id, "-o", output], None).map(|_| ())
+    }
+}
+
+fn on_path(binary: &str) -> bool {
+    match env::var_os("PATH") {
+        Some(paths) => env::split_paths(&paths).any(|p| Path::new(&p).join(binary).is_file()),
+        None => false,
+    }
+}
+
+pub fn for_name(name: &str) -> Option<Box<dyn ImageSource>> {
+    match name {
+        DOCKER => Some(Box::new(DockerCli::new(DOCKER))),
+        DOCKER_API => Some(Box::new(Engine::local())),
+        PODMAN => Some(Box::new(DockerCli::new(PODMAN))),
+        NERDCTL => Some(Box::new(DockerCli::new(NERDCTL))),
+        CTR => Some(Box::new(Ctr {})),
+        _ => None,
+    }
+}
+
+// Picks the docker engine when its socket is there, else the first runtime found on PATH
+pub fn detect() -> Option<Box<dyn ImageSource>> {
+    if engine::local_socket().is_some_and(|s| s.exists()) {
+        trace!("Detected image source: {}", DOCKER_API);
+        return for_name(DOCKER_API);
+    }
+    let name = DETECT_ORDER.iter().find(|b| on_path(b))?;
+    trace!("Detected image source: {}", name);
+    for_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::docker::cli;
+    use crate::docker::source::{for_name, DockerCli, ImageSource, CTR, DOCKER, PODMAN};
+    use crate::error::Error;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+    use std::time::Duration;
+
+    // Behaves like the docker cli for the commands cake runs
+    const FAKE_DOCKER: &str = r#"#!/bin/sh
+case "$1" in
+image)
+    if [ "$3" = "app" ]; then
+        echo '[{"Id":"sha256:app","RepoTags":["app:latest"],"Os":"linux","Architecture":"amd64"}]'
+    else
+        echo '[]'
+        echo "Error: No such image: $3" >&2
+        exit 1
+    fi ;;
+save)
+    echo "Error response from daemon: no space left on device" >&2
+    exit 1 ;;
+run)
+    for arg in "$@"; do
+        case "$arg" in
+        -it|-t|--tty)
+            echo "the input device is not a TTY" >&2
+            exit 1 ;;
+        esac
+    done
+    printf 'bash\t5.1-6\t100\n' ;;
+sleep)
+    exec sleep 5 ;;
+esac
+"#;
+
+    #[test]
+    fn creates_source_for_name() {
+        assert_eq!(for_name(PODMAN).unwrap().name(), "podman");
+        assert_eq!(for_name(CTR).unwrap().name(), "ctr");
+        assert!(for_name("lxc").is_none());
+    }
+
+    #[test]
+    fn runs_fake_docker() {
+        let dir = tempfile::tempdir().unwrap();
+        let script = dir.path().join(DOCKER);
+        fs::write(&script, FAKE_DOCKER).unwrap();
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+        let script = script.to_str().unwrap();
+
+        let docker = DockerCli::at(DOCKER, script);
+        assert_eq!(docker.inspect("app").unwrap().id, "sha256:app");
+        assert!(matches!(
+            docker.inspect("web"),
+            Err(Error::ImageNotFound(m)) if m == "Error: No such image: web"
+        ));
+
+        let error = docker.save("app", "app.tar", None).unwrap_err();
+        assert!(matches!(error, Error::Command(_)));
+        assert!(error.to_string().contains("no space left on device"));
+
+        assert_eq!(
+            docker.run("app", vec!["rpm"]).unwrap(),
+            "bash\t5.1-6\t100\n"
+        );
+
+        let timeout = Some(Duration::from_millis(200));
+        // A stuck container is not a missing daemon
+        let error = cli::exec(script, vec!["sleep"], timeout).unwrap_err();
+        assert!(matches!(error, Error::Command(m) if m.ends_with("did not finish within 200ms")));
+    }
+}
@@ -0,0 +1,98 @@
+// Errors which end the analysis. Each kind maps to its own exit code so
+// scripts can tell a missing image from a broken daemon or cache.
+use std::fmt;
+use std::io;
+
+pub const EXIT_FAILURE: i32 = 1;
+pub const EXIT_IMAGE_NOT_FOUND: i32 = 3;
+pub const EXIT_DAEMON_UNAVAILABLE: i32 = 4;
+pub const EXIT_CORRUPT_ARCHIVE: i32 = 5;
+pub const EXIT_CACHE_INVALID: i32 = 6;
+
+#[derive(Debug)]
+pub enum Error {
+    ImageNotFound(String),
+    DaemonUnavailable(String),
+    CorruptArchive(String),
+    CacheInvalid(String),
+    Registry(String),
+    Command(String),
+    Io { context: String, source: io::Error },
+    Invalid(String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::ImageNotFound(_) => EXIT_IMAGE_NOT_FOUND,
+            Error::DaemonUnavailable(_) => EXIT_DAEMON_UNAVAILABLE,
+            Error::CorruptArchive(_) => EXIT_CORRUPT_ARCHIVE,
+            Error::CacheInvalid(_) => EXIT_CACHE_INVALID,
+            _ => EXIT_FAILURE,
+        }
+    }
+
+    pub fn corrupt(context: &str, error: impl fmt::Display) -> Error {
+        Error::CorruptArchive(format!("{}: {}", context, error))
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::ImageNotFound(message) => write!(f, "Image not found: {}", message),
+            Error::DaemonUnavailable(message) => write!(f, "{}", message),
+            Error::CorruptArchive(message) => write!(f, "Corrupt image: {}", message),
+            Error::CacheInvalid(message) => {
+                write!(f, "{}, remove it with `cake cache clear`", message)
+            }
+            Error::Registry(message) => write!(f, "{}", message),
+            Error::Command(message) => write!(f, "{}", message),
+            Error::Io { context, source } => write!(f, "{}: {}", context, source),
+            Error::Invalid(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+// Adds what was being done to an io::Error
+pub trait Context<T> {
+    fn context<C: fmt::Display>(self, context: C) -> Result<T>;
+}
+
+impl<T> Context<T> for io::Result<T> {
+    fn context<C: fmt::Display>(self, context: C) -> Result<T> {
+        self.map_err(|source| Error::Io {
+            context: context.to_string(),
+            source,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::{Context, Error, EXIT_CACHE_INVALID, EXIT_FAILURE};
+    use std::fs;
+
+    #[test]
+    fn adds_context_and_exit_codes() {
+        let error = fs::read("does/not/exist")
+            .context("Failed to read tree")
+            .unwrap_err();
+        assert!(error.to_string().starts_with("Failed to read tree: "));
+        assert_eq!(error.exit_code(), EXIT_FAILURE);
+
+        let error = Error::CacheInvalid("Cached report is invalid".to_string());
+        assert_eq!(error.exit_code(), EXIT_CACHE_INVALID);
+    }
+}
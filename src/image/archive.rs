@@ -0,0 +1,200 @@
+// Reads an image archive in place. One pass over the tar headers records where
+// every file starts, after which layers are read by seeking to them.
+use super::image_tar::{decompress, is_compressed};
+use super::store::ImageStore;
+use crate::ofs::layer::{ArchiveEntry, Layer};
+use log::{info, trace};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, Error, ErrorKind, Read, Result, Seek, SeekFrom};
+use std::path::Path;
+use tar::{Archive, EntryType};
+use tempfile::NamedTempFile;
+
+// docker save stores a layer shared by several images once and links the rest
+const MAX_LINKS: usize = 8;
+
+pub struct ImageArchive {
+    path: String,
+    entries: HashMap<String, ArchiveEntry>,
+    links: HashMap<String, String>,
+}
+
+fn normalize(path: &str) -> String {
+    path.trim_start_matches("./")
+        .trim_end_matches('/')
+        .to_string()
+}
+
+// Resolves a symlink target relative to the directory of the link
+fn resolve_link(name: &str, target: &str) -> String {
+    let mut parts: Vec<&str> = name.split('/').collect();
+    parts.pop();
+    for p in target.split('/') {
+        match p {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            p => parts.push(p),
+        }
+    }
+    parts.join("/")
+}
+
+pub fn open_entry(path: &str, entry: ArchiveEntry) -> Result<impl Read> {
+    let mut file = File::open(path)?;
+    file.seek(SeekFrom::Start(entry.offset))?;
+    Ok(file.take(entry.length))
+}
+
+pub fn is_compressed_file(path: &Path) -> bool {
+    let mut magic = [0; 4];
+    match File::open(path).and_then(|mut f| f.read(&mut magic)) {
+        Ok(n) => is_compressed(&magic[..n]),
+        Err(_) => false,
+    }
+}
+
+// A `docker save | gzip` can not be read in place, it is unpacked to a
+// temporary file which is removed when dropped
+pub fn unpack(path: &str) -> Result<NamedTempFile> {
+    info!("Unpacking {}", path);
+    let mut unpacked = tempfile::Builder::new()
+        .prefix("cake-")
+        .suffix(".tar")
+        .tempfile()?;
+    io::copy(&mut decompress(File::open(path)?)?, &mut unpacked)?;
+    Ok(unpacked)
+}
+
+impl ImageArchive {
+    pub fn open(path: &str) -> Result<ImageArchive> {
+        trace!("Indexing image archive {}", path);
+        let mut archive = Archive::new(File::open(path)?);
+        let mut entries = HashMap::new();
+        let mut links = HashMap::new();
+
+        for e in archive.entries_with_seek()? {
+            let e = e?;
+            let name = normalize(&e.path()?.to_string_lossy());
+            match e.header().entry_type() {
+                EntryType::Regular => {
+                    let entry = ArchiveEntry {
+                        offset: e.raw_file_position(),
+                        length: e.size(),
+                    };
+                    entries.insert(name, entry);
+                }
+                EntryType::Symlink | EntryType::Link => {
+                    if let Some(target) = e.link_name()? {
+                        let target = target.to_string_lossy();
+                        let resolved = if e.header().entry_type() == EntryType::Link {
+                            normalize(&target)
+                        } else {
+                            resolve_link(&name, &target)
+                        };
+                        links.insert(name, resolved);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Ok(ImageArchive {
+            path: path.to_string(),
+            entries,
+            links,
+        })
+    }
+
+    pub fn entry(&self, name: &str) -> Option<ArchiveEntry> {
+        let mut name = normalize(name);
+        for _ in 0..MAX_LINKS {
+            if let Some(entry) = self.entries.get(&name) {
+                return Some(*entry);
+            }
+            name = self.links.get(&name)?.clone();
+        }
+        None
+    }
+
+    fn find(&self, name: &str) -> Result<ArchiveEntry> {
+        self.entry(name).ok_or_else(|| {
+            Error::new(
+                ErrorKind::NotFound,
+                format!("{} not found in {}", name, self.path),
+            )
+        })
+    }
+}
+
+impl ImageStore for ImageArchive {
+    fn exists(&self, name: &str) -> bool {
+        self.entry(name).is_some()
+    }
+
+    fn open(&self, name: &str) -> Result<Box<dyn Read + '_>> {
+        Ok(Box::new(open_entry(&self.path, self.find(name)?)?))
+    }
+
+    fn layer(&self, index: usize, name: &str) -> Result<Layer> {
+        Ok(Layer::in_archive(
+            index,
+            self.path.clone(),
+            self.find(name)?,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::image::archive::{resolve_link, ImageArchive};
+    use crate::image::manifest::Manifest;
+    use crate::image::store::ImageStore;
+    use tar::{Builder, EntryType, Header};
+
+    #[test]
+    fn resolves_relative_links() {
+        assert_eq!(
+            resolve_link("abc/layer.tar", "../def/layer.tar"),
+            "def/layer.tar"
+        );
+        assert_eq!(
+            resolve_link("abc/layer.tar", "./other.tar"),
+            "abc/other.tar"
+        );
+    }
+
+    #[test]
+    fn reads_entries_in_place() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("image.tar");
+
+        let mut builder = Builder::new(std::fs::File::create(&path).unwrap());
+        builder.append_dir_all(".", "test_files/oci").unwrap();
+        let mut header = Header::new_gnu();
+        header.set_entry_type(EntryType::Symlink);
+        header.set_size(0);
+        builder
+            .append_link(
+                &mut header,
+                "shared/layer.tar",
+                "../blobs/sha256/9d88a2580b57ae893043614360747707059c6ec8278753f2177bb9616bc5a202",
+            )
+            .unwrap();
+        builder.finish().unwrap();
+
+        let archive = ImageArchive::open(path.to_str().unwrap()).unwrap();
+        let manifest = Manifest::for_store(&archive).unwrap();
+        assert_eq!(manifest.layers.len(), 2);
+
+        let entry = archive.entry(&manifest.layers[0]).unwrap();
+        assert_eq!(archive.entry("shared/layer.tar"), Some(entry));
+        assert!(archive
+            .read_to_string("oci-layout")
+            .unwrap()
+            .contains("1.0.0"));
+        assert!(!archive.exists("manifest.json"));
+    }
+}
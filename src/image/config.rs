@@ -0,0 +1,71 @@
+// The image config blob, https://github.com/opencontainers/image-spec/blob/main/config.md
+use crate::error::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+// Shared by the config blob and `docker image inspect`
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase", default)]
+pub struct ContainerConfig {
+    pub user: String,
+    pub env: Option<Vec<String>>,
+    pub cmd: Option<Vec<String>>,
+    pub entrypoint: Option<Vec<String>>,
+    pub working_dir: String,
+    pub labels: Option<HashMap<String, String>>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConfigRootFs {
+    pub r#type: String,
+    pub diff_ids: Vec<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct History {
+    pub created: Option<String>,
+    pub created_by: Option<String>,
+    pub comment: Option<String>,
+    pub empty_layer: bool,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ImageConfig {
+    pub architecture: String,
+    pub os: String,
+    pub created: Option<String>,
+    pub config: Option<ContainerConfig>,
+    pub rootfs: ConfigRootFs,
+    pub history: Vec<History>,
+}
+
+impl ImageConfig {
+    pub fn from_slice(content: &[u8]) -> Result<ImageConfig> {
+        serde_json::from_slice(content).map_err(|e| Error::corrupt("Invalid image config", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::image::config::ImageConfig;
+
+    #[test]
+    fn reads_history_from_config() {
+        let content = std::fs::read(
+            "test_files/oci/blobs/sha256/15c57637dc26ff3b3502da721493bdd947c472d3e3f3857753d482557641ccff",
+        )
+        .unwrap();
+        let config = ImageConfig::from_slice(&content).unwrap();
+
+        assert_eq!(config.rootfs.diff_ids.len(), 2);
+        assert_eq!(config.history.len(), 3);
+        assert!(config.history[1].empty_layer);
+        assert_eq!(
+            config.config.unwrap().cmd,
+            Some(vec!["/bin/sh".to_string()])
+        );
+    }
+}
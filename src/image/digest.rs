@@ -0,0 +1,72 @@
+use crate::error::{Error, Result};
+use sha2::{Digest, Sha256};
+use std::io::{self, Read};
+
+pub fn sha256_digest(content: &[u8]) -> String {
+    format!("sha256:{:x}", Sha256::digest(content))
+}
+
+// Hashes everything read through it
+pub struct HashingReader<R: Read> {
+    reader: R,
+    hasher: Sha256,
+}
+
+impl<R: Read> HashingReader<R> {
+    pub fn new(reader: R) -> HashingReader<R> {
+        HashingReader {
+            reader,
+            hasher: Sha256::new(),
+        }
+    }
+
+    // Reads what is left, a tar reader stops at the end-of-archive marker
+    pub fn finish(mut self) -> io::Result<String> {
+        io::copy(&mut self, &mut io::sink())?;
+        Ok(format!("sha256:{:x}", self.hasher.finalize()))
+    }
+}
+
+impl<R: Read> Read for HashingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.reader.read(buf)?;
+        self.hasher.update(&buf[..n]);
+        Ok(n)
+    }
+}
+
+pub fn is_sha256_digest(digest: &str) -> bool {
+    match digest.strip_prefix("sha256:") {
+        Some(hex) => hex.len() == 64 && hex.chars().all(|c| c.is_ascii_hexdigit()),
+        None => false,
+    }
+}
+
+pub fn verify_digest(what: &str, actual: &str, expected: &str) -> Result<()> {
+    if actual != expected {
+        return Err(Error::CorruptArchive(format!(
+            "Digest of {} is {}, expected {}",
+            what, actual, expected
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::image::digest::{is_sha256_digest, sha256_digest, HashingReader};
+    use std::io::Read;
+
+    #[test]
+    fn hashes_while_reading() {
+        let content = b"layer content";
+        let mut reader = HashingReader::new(&content[..]);
+        let mut start = [0; 5];
+        reader.read_exact(&mut start).unwrap();
+
+        let digest = reader.finish().unwrap();
+        assert_eq!(digest, sha256_digest(content));
+        assert!(is_sha256_digest(&digest));
+        assert!(!is_sha256_digest("sha256:app"));
+    }
+}
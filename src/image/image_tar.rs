@@ -1,51 +1,595 @@
+use flate2::read::MultiGzDecoder;
 use log::trace;
+use std::collections::{BTreeMap, HashMap};
 use std::fs::File;
-use tar::{Archive, EntryType};
+use tar::{Archive, Entry, EntryType};
 
-use super::super::ofs::ofs::OverlayFs;
-use std::io::Read;
+use super::super::ofs::file_info::{Link, Metadata};
+use super::super::ofs::layer::Layer;
+use super::super::ofs::ofs::{is_whiteout, OverlayFs, WHITEOUT};
+use super::super::ofs::utils::split_last_entry;
+use super::archive::open_entry;
+use super::digest::{verify_digest, HashingReader};
+use super::rootfs;
+use crate::docker::container::{Change, ChangeKind};
+use crate::error::{Context, Error, Result};
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::Path;
 
-pub fn extract_image_tar(id: &str) {
-    let file = File::open(format!("{}.tar", id)).unwrap();
-    let mut archive = Archive::new(file);
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
 
-    archive.unpack(id).unwrap();
+pub fn is_compressed(magic: &[u8]) -> bool {
+    magic.starts_with(&GZIP_MAGIC) || magic.starts_with(&ZSTD_MAGIC)
 }
 
-pub fn read_tar_layer(ofs: &mut OverlayFs, path: &str, layer_id: &str) {
-    let file = File::open(path).unwrap();
-    let mut a = Archive::new(file);
+// Layer blobs in OCI layouts and registries are usually tar+gzip or tar+zstd,
+// sniff the magic bytes and decompress while reading.
+pub fn decompress<'a, R: Read + 'a>(reader: R) -> std::io::Result<Box<dyn Read + 'a>> {
+    let mut reader = BufReader::new(reader);
+    let magic = reader.fill_buf()?;
 
-    for file in a.entries().unwrap() {
-        let entry = file.unwrap();
-        let header = entry.header();
-        let cow = header.path().unwrap();
-        let path_str = cow.to_str().unwrap();
+    if magic.starts_with(&GZIP_MAGIC) {
+        trace!("Reading gzip compressed layer");
+        Ok(Box::new(MultiGzDecoder::new(reader)))
+    } else if magic.starts_with(&ZSTD_MAGIC) {
+        trace!("Reading zstd compressed layer");
+        Ok(Box::new(zstd::Decoder::with_buffer(reader)?))
+    } else {
+        Ok(Box::new(reader))
+    }
+}
+
+// Layers of a saved image are read in place from the image archive
+fn open_blob(layer: &Layer) -> Result<Box<dyn Read>> {
+    let blob: Box<dyn Read> = match layer.entry {
+        Some(entry) => Box::new(open_entry(&layer.path, entry).context(&layer.path)?),
+        None => Box::new(File::open(&layer.path).context(&layer.path)?),
+    };
+    Ok(blob)
+}
+
+fn open_layer(layer: &Layer) -> Result<Box<dyn Read>> {
+    decompress(open_blob(layer)?).map_err(|e| Error::corrupt(&layer.path, e))
+}
 
-        match header.entry_type() {
-            EntryType::Directory => ofs.add_dir(path_str),
+// Names in a tar are bytes, show the ones which are not UTF-8 as lossy strings.
+// Tarballs made with `tar -C dir .` put ./ in front of every name.
+fn entry_path<R: Read>(entry: &Entry<R>) -> String {
+    let path = String::from_utf8_lossy(&entry.path_bytes()).into_owned();
+    match path.strip_prefix("./") {
+        Some(path) => path.to_string(),
+        None => path,
+    }
+}
+
+// What a layer adds to the file system, kept so that a layer shared by
+// several images in one archive is only parsed once
+#[derive(Debug, PartialEq)]
+pub enum LayerEntry {
+    Dir(String, Metadata),
+    File(String, u64, Metadata),
+    Symlink(String, String, Metadata),
+    Hardlink(String, String, Metadata),
+}
+
+fn link_target<R: Read>(entry: &Entry<R>) -> String {
+    let target = entry.link_name_bytes().unwrap_or_default();
+    String::from_utf8_lossy(&target).into_owned()
+}
 
-            EntryType::Regular => ofs.add_file(path_str, header.size().unwrap(), layer_id),
-            _ => {}
+// Extended attributes are stored as PAX records with this prefix
+const XATTR_PREFIX: &str = "SCHILY.xattr.";
+
+// Some tools leave the numeric fields empty, those are read as 0
+fn entry_metadata<R: Read>(entry: &mut Entry<R>) -> io::Result<Metadata> {
+    let header = entry.header();
+    let mut metadata = Metadata {
+        mode: header.mode().unwrap_or(0),
+        uid: header.uid().unwrap_or(0),
+        gid: header.gid().unwrap_or(0),
+        mtime: header.mtime().unwrap_or(0),
+        xattrs: BTreeMap::new(),
+        digest: None,
+    };
+    if let Some(extensions) = entry.pax_extensions()? {
+        for extension in extensions {
+            let extension = extension?;
+            if let Some(name) = extension
+                .key()
+                .ok()
+                .and_then(|k| k.strip_prefix(XATTR_PREFIX))
+            {
+                metadata
+                    .xattrs
+                    .insert(name.to_string(), extension.value_bytes().to_vec());
+            }
         }
     }
+    Ok(metadata)
+}
+
+// Everything but devices and fifos, they take no space. Hashing the content
+// of regular files reads them, which is most of the time spent on a layer.
+fn layer_entry<R: Read>(
+    entry: &mut Entry<R>,
+    path: String,
+    hash: bool,
+) -> io::Result<Option<LayerEntry>> {
+    let mut metadata = entry_metadata(entry)?;
+    let kind = entry.header().entry_type();
+    if hash && kind == EntryType::Regular {
+        metadata.digest = Some(HashingReader::new(&mut *entry).finish()?);
+    }
+    let header = entry.header();
+    Ok(match kind {
+        EntryType::Directory => Some(LayerEntry::Dir(path, metadata)),
+        EntryType::Regular => Some(LayerEntry::File(path, header.size()?, metadata)),
+        EntryType::Symlink => Some(LayerEntry::Symlink(path, link_target(entry), metadata)),
+        // Hardlinks name their target from the root of the tar
+        EntryType::Link => {
+            let target = link_target(entry);
+            let target = target.strip_prefix("./").unwrap_or(&target).to_string();
+            Some(LayerEntry::Hardlink(path, target, metadata))
+        }
+        // Whiteouts written as character devices by some tools
+        _ if is_whiteout(&path) => Some(LayerEntry::File(path, 0, metadata)),
+        _ => None,
+    })
 }
 
-pub fn get_file_from_layer(os_path: &str, file_path: &str) -> Option<String> {
-    trace!("Searching file: {} in layer: {}", file_path, os_path);
-    let file = File::open(os_path).unwrap();
-    let mut a = Archive::new(file);
+// sha256 of the blob as stored and of the uncompressed tar, its diff_id
+pub struct LayerDigests {
+    pub blob: String,
+    pub diff_id: String,
+}
 
-    for f in a.entries().unwrap() {
-        let mut entry = f.unwrap();
+pub struct ParsedLayer {
+    pub entries: Vec<LayerEntry>,
+    pub digests: LayerDigests,
+}
+
+impl LayerDigests {
+    // The blob digest is only known for content addressed layers
+    pub fn verify(&self, name: &str, blob: Option<&str>, diff_id: Option<&str>) -> Result<()> {
+        if let Some(blob) = blob {
+            verify_digest(&format!("layer {}", name), &self.blob, blob)?;
+        }
+        if let Some(diff_id) = diff_id {
+            verify_digest(
+                &format!("uncompressed layer {}", name),
+                &self.diff_id,
+                diff_id,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+pub fn parse_tar_layer(layer: &Layer, hash: bool) -> Result<ParsedLayer> {
+    parse_blob(open_blob(layer)?, hash).map_err(|e| Error::corrupt(&layer.path, e))
+}
+
+// Hashes the blob and the uncompressed tar while reading the entries
+pub fn parse_blob<R: Read>(blob: R, hash: bool) -> io::Result<ParsedLayer> {
+    let mut blob = HashingReader::new(blob);
+    let mut tar = HashingReader::new(decompress(&mut blob)?);
+    let entries = parse_layer(&mut tar, hash)?;
+    let diff_id = tar.finish()?;
+
+    Ok(ParsedLayer {
+        entries,
+        digests: LayerDigests {
+            blob: blob.finish()?,
+            diff_id,
+        },
+    })
+}
+
+// With hash the content of every file is hashed too
+pub fn parse_layer<R: Read>(layer: R, hash: bool) -> io::Result<Vec<LayerEntry>> {
+    let mut a = Archive::new(layer);
+    let mut entries = vec![];
+
+    for file in a.entries()? {
+        let mut entry = file?;
+        let path = entry_path(&entry);
+        entries.extend(layer_entry(&mut entry, path, hash)?);
+    }
+    Ok(entries)
+}
+
+// The entries of a container's writable layer. The export holds the whole
+// filesystem, only the paths the container changed are kept and the deleted
+// ones become whiteouts.
+pub fn parse_container_export<R: Read>(
+    export: R,
+    changes: &[Change],
+    hash: bool,
+) -> io::Result<Vec<LayerEntry>> {
+    let mut entries = vec![];
+    let mut changed = HashMap::new();
+    for change in changes {
+        let path = change.path.trim_start_matches('/');
+        if change.kind == ChangeKind::Deleted {
+            let (dir, name) = split_last_entry(path);
+            entries.push(LayerEntry::File(
+                format!("{}{}{}", dir, WHITEOUT, name),
+                0,
+                Metadata::default(),
+            ));
+        } else {
+            changed.insert(path.to_string(), change.kind);
+        }
+    }
+
+    let mut a = Archive::new(export);
+    for file in a.entries()? {
+        let mut entry = file?;
+        let path = entry_path(&entry);
+        let path = path.trim_end_matches('/');
+        let kind = match changed.get(path) {
+            Some(kind) => *kind,
+            None => continue,
+        };
+
+        match layer_entry(&mut entry, path.to_string(), hash)? {
+            // Directories only show up as modified because of their contents
+            Some(LayerEntry::Dir(path, metadata)) if kind == ChangeKind::Added => {
+                entries.push(LayerEntry::Dir(format!("{}/", path), metadata))
+            }
+            Some(LayerEntry::Dir(..)) | None => {}
+            Some(entry) => entries.push(entry),
+        }
+    }
+    Ok(entries)
+}
+
+impl LayerEntry {
+    fn path(&self) -> &str {
+        match self {
+            LayerEntry::Dir(path, _)
+            | LayerEntry::File(path, _, _)
+            | LayerEntry::Symlink(path, _, _)
+            | LayerEntry::Hardlink(path, _, _) => path,
+        }
+    }
+}
 
-        let cow = entry.path().unwrap();
-        let path_str = cow.to_str().unwrap();
-        if path_str == file_path {
+// Whiteouts can come anywhere in the tar but only hide the lower layers,
+// so they are applied before the rest of the layer
+pub fn apply_layer(ofs: &mut OverlayFs, entries: &[LayerEntry], layer_id: &str) -> Result<()> {
+    let (whiteouts, entries): (Vec<_>, Vec<_>) =
+        entries.iter().partition(|e| is_whiteout(e.path()));
+    for whiteout in whiteouts {
+        ofs.apply_whiteout(whiteout.path(), layer_id)?;
+    }
+
+    for entry in entries {
+        match entry {
+            LayerEntry::Dir(path, metadata) => {
+                ofs.add_dir(path, layer_id, Some(metadata.clone()))?
+            }
+            LayerEntry::File(path, size, metadata) => {
+                ofs.add_entry(path, *size, layer_id, None, Some(metadata.clone()))?
+            }
+            LayerEntry::Symlink(path, target, metadata) => ofs.add_entry(
+                path,
+                0,
+                layer_id,
+                Some(Link::symlink(target)),
+                Some(metadata.clone()),
+            )?,
+            LayerEntry::Hardlink(path, target, metadata) => ofs.add_entry(
+                path,
+                0,
+                layer_id,
+                Some(Link::hardlink(target)),
+                Some(metadata.clone()),
+            )?,
+        }
+    }
+    Ok(())
+}
+
+pub fn get_file_from_layer(layer: &Layer, file_path: &str) -> Result<Option<String>> {
+    trace!("Searching file: {} in layer: {}", file_path, layer.path);
+    // The layer of a root filesystem directory is the directory itself
+    if Path::new(&layer.path).is_dir() {
+        return rootfs::read_file(Path::new(&layer.path), file_path);
+    }
+    let mut a = Archive::new(open_layer(layer)?);
+    let corrupt = |e| Error::corrupt(&layer.path, e);
+
+    for f in a.entries().map_err(corrupt)? {
+        let mut entry = f.map_err(corrupt)?;
+
+        if entry_path(&entry) == file_path {
             let mut s = String::new();
-            entry.read_to_string(&mut s).unwrap();
-            return Some(s);
+            entry
+                .read_to_string(&mut s)
+                .map_err(|e| Error::corrupt(file_path, e))?;
+            return Ok(Some(s));
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::image::digest::sha256_digest;
+    use crate::image::image_tar::{
+        apply_layer, get_file_from_layer, parse_layer, parse_tar_layer, LayerEntry,
+    };
+    use crate::ofs::file_info::Metadata;
+    use crate::ofs::layer::Layer;
+    use crate::ofs::ofs::OverlayFs;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::fs::File;
+    use std::io::Write;
+    use tar::{Builder, EntryType, Header};
+
+    const LAYER: &str =
+        "test_files/oci/blobs/sha256/9d88a2580b57ae893043614360747707059c6ec8278753f2177bb9616bc5a202";
+
+    fn read_layer(layer: &Layer) -> OverlayFs {
+        let mut ofs = OverlayFs::new();
+        apply_layer(
+            &mut ofs,
+            &parse_tar_layer(layer, false).unwrap().entries,
+            &layer.layer_id,
+        )
+        .unwrap();
+        ofs
+    }
+
+    #[test]
+    fn reads_gzip_and_zstd_layers() {
+        let dir = tempfile::tempdir().unwrap();
+        let layer = std::fs::read(LAYER).unwrap();
+
+        let gzip_path = dir.path().join("layer.tar.gz");
+        let mut gz = GzEncoder::new(File::create(&gzip_path).unwrap(), Compression::default());
+        gz.write_all(&layer).unwrap();
+        gz.finish().unwrap();
+
+        let zstd_path = dir.path().join("layer.tar.zst");
+        let zst = zstd::encode_all(&layer[..], 3).unwrap();
+        File::create(&zstd_path).unwrap().write_all(&zst).unwrap();
+
+        for path in [
+            LAYER,
+            gzip_path.to_str().unwrap(),
+            zstd_path.to_str().unwrap(),
+        ]
+        .iter()
+        {
+            let layer = Layer::new(0, path.to_string());
+            let ofs = read_layer(&layer);
+            assert_eq!(ofs.get_file("/bin/busybox").unwrap().size, 1000);
+            let digests = parse_tar_layer(&layer, false).unwrap().digests;
+            assert_eq!(
+                digests.diff_id,
+                format!("sha256:{}", &LAYER[LAYER.len() - 64..])
+            );
+            assert!(digests.verify(path, None, Some(&digests.diff_id)).is_ok());
+            assert!(digests.verify(path, Some("sha256:0"), None).is_err());
+            assert_eq!(
+                get_file_from_layer(&layer, "etc/os-release")
+                    .unwrap()
+                    .unwrap(),
+                "ID=alpine\nVERSION_ID=3.13.5\n"
+            );
+        }
+    }
+
+    fn append_link(builder: &mut Builder<Vec<u8>>, kind: EntryType, path: &str, target: &str) {
+        let mut header = Header::new_gnu();
+        header.set_entry_type(kind);
+        header.set_size(0);
+        builder.append_link(&mut header, path, target).unwrap();
+    }
+
+    #[test]
+    fn follows_symlinks_and_counts_hardlinks_once() {
+        let mut builder = Builder::new(vec![]);
+        let mut header = Header::new_gnu();
+        header.set_size(22);
+        builder
+            .append_data(
+                &mut header,
+                "usr/lib/os-release",
+                &b"ID=debian\nVERSION_ID=11"[..22],
+            )
+            .unwrap();
+        let mut header = Header::new_gnu();
+        header.set_size(100);
+        builder
+            .append_data(&mut header, "usr/bin/perl", vec![0; 100].as_slice())
+            .unwrap();
+        append_link(
+            &mut builder,
+            EntryType::Symlink,
+            "etc/os-release",
+            "../usr/lib/os-release",
+        );
+        append_link(&mut builder, EntryType::Symlink, "lib", "usr/lib");
+        append_link(
+            &mut builder,
+            EntryType::Link,
+            "usr/bin/perl5",
+            "usr/bin/perl",
+        );
+        append_link(&mut builder, EntryType::Symlink, "loop/a", "b");
+        append_link(&mut builder, EntryType::Symlink, "loop/b", "a");
+        let tar = builder.into_inner().unwrap();
+
+        let entries = parse_layer(tar.as_slice(), false).unwrap();
+        assert!(entries.contains(&LayerEntry::Symlink(
+            "etc/os-release".to_string(),
+            "../usr/lib/os-release".to_string(),
+            Metadata::default()
+        )));
+        assert!(entries.contains(&LayerEntry::Hardlink(
+            "usr/bin/perl5".to_string(),
+            "usr/bin/perl".to_string(),
+            Metadata::default()
+        )));
+
+        let mut ofs = OverlayFs::new();
+        apply_layer(&mut ofs, &entries, "layer0").unwrap();
+        ofs.update_sizes();
+        assert_eq!(ofs.size(), 122);
+        assert_eq!(ofs.get_dir("/usr/bin").unwrap().size(), 100);
+
+        assert_eq!(
+            ofs.resolve_path("/etc/os-release").unwrap(),
+            "/usr/lib/os-release"
+        );
+        assert_eq!(
+            ofs.resolve_path("/lib/os-release").unwrap(),
+            "/usr/lib/os-release"
+        );
+        assert_eq!(ofs.resolve_path("/usr/bin/perl5").unwrap(), "/usr/bin/perl");
+        assert_eq!(ofs.resolve_path("/usr/bin/perl").unwrap(), "/usr/bin/perl");
+        assert!(ofs.resolve_path("/loop/a").is_none());
+    }
+
+    fn layer(entries: &[(&str, EntryType, u64)]) -> Vec<LayerEntry> {
+        let mut builder = Builder::new(vec![]);
+        for (path, kind, size) in entries {
+            let mut header = Header::new_gnu();
+            header.set_entry_type(*kind);
+            header.set_size(*size);
+            builder
+                .append_data(&mut header, path, vec![0; *size as usize].as_slice())
+                .unwrap();
+        }
+        parse_layer(builder.into_inner().unwrap().as_slice(), false).unwrap()
+    }
+
+    #[test]
+    fn applies_whiteouts_to_lower_layers_only() {
+        let mut ofs = OverlayFs::new();
+        let base = layer(&[
+            ("var/lib/apt/lists/", EntryType::Directory, 0),
+            ("var/lib/apt/lists/main", EntryType::Regular, 100),
+            ("var/lib/apt/lists/security", EntryType::Regular, 200),
+            ("var/cache/apt/pkgcache.bin", EntryType::Regular, 300),
+            ("etc/motd", EntryType::Regular, 10),
+        ]);
+        apply_layer(&mut ofs, &base, "layer0").unwrap();
+
+        // rm -rf /var/lib/apt/lists && apt-get update, the marker comes last
+        let update = layer(&[
+            ("var/lib/apt/lists/main", EntryType::Regular, 50),
+            ("var/lib/apt/lists/.wh..wh..opq", EntryType::Regular, 0),
+            ("var/cache/.wh.apt/", EntryType::Directory, 0),
+            ("etc/.wh.motd", EntryType::Char, 0),
+        ]);
+        apply_layer(&mut ofs, &update, "layer1").unwrap();
+        ofs.update_sizes();
+
+        let main = ofs.get_file("/var/lib/apt/lists/main").unwrap();
+        assert!(!main.is_deleted());
+        assert_eq!(main.versions.len(), 2);
+        assert_eq!(main.versions[1].size, 50);
+        assert!(ofs
+            .get_file("/var/lib/apt/lists/security")
+            .unwrap()
+            .is_deleted());
+        assert!(ofs.get_file("/var/lib/apt/lists/.wh..wh..opq").is_none());
+        assert!(ofs
+            .get_file("/var/cache/apt/pkgcache.bin")
+            .unwrap()
+            .is_deleted());
+        assert!(ofs.get_dir("/var/cache/.wh.apt").is_none());
+        assert!(ofs.get_file("/etc/motd").unwrap().is_deleted());
+
+        // Deleting what is already gone adds no version
+        apply_layer(
+            &mut ofs,
+            &layer(&[("var/.wh.cache", EntryType::Regular, 0)]),
+            "layer2",
+        )
+        .unwrap();
+        let cache = ofs.get_file("/var/cache/apt/pkgcache.bin").unwrap();
+        assert_eq!(cache.versions.len(), 1);
+    }
+
+    // A PAX record is "<length> <key>=<value>\n", the length counts itself
+    fn pax_record(key: &str, value: &[u8]) -> Vec<u8> {
+        let rest = key.len() + value.len() + 3;
+        let mut length = rest + 1;
+        while (rest + length.to_string().len()) != length {
+            length += 1;
         }
+        let mut record = format!("{} {}=", length, key).into_bytes();
+        record.extend_from_slice(value);
+        record.push(b'\n');
+        record
+    }
+
+    #[test]
+    fn keeps_metadata_of_every_version() {
+        let mut builder = Builder::new(vec![]);
+        let capability = [1, 0, 0, 2, 0, 32, 0, 0];
+        let pax = pax_record("SCHILY.xattr.security.capability", &capability);
+        let mut header = Header::new_ustar();
+        header.set_entry_type(EntryType::XHeader);
+        header.set_size(pax.len() as u64);
+        builder
+            .append_data(&mut header, "PaxHeaders/ping", pax.as_slice())
+            .unwrap();
+        let mut header = Header::new_ustar();
+        header.set_size(4);
+        header.set_mode(0o4755);
+        header.set_uid(0);
+        header.set_gid(0);
+        header.set_mtime(1_600_000_000);
+        builder
+            .append_data(&mut header, "bin/ping", &b"ping"[..])
+            .unwrap();
+        let base = parse_layer(builder.into_inner().unwrap().as_slice(), true).unwrap();
+
+        let mut builder = Builder::new(vec![]);
+        let mut header = Header::new_ustar();
+        header.set_size(4);
+        header.set_mode(0o755);
+        header.set_uid(1000);
+        header.set_gid(1000);
+        header.set_mtime(1_700_000_000);
+        builder
+            .append_data(&mut header, "bin/ping", &b"ping"[..])
+            .unwrap();
+        let chmod = parse_layer(builder.into_inner().unwrap().as_slice(), true).unwrap();
+
+        let mut ofs = OverlayFs::new();
+        apply_layer(&mut ofs, &base, "layer0").unwrap();
+        apply_layer(&mut ofs, &chmod, "layer1").unwrap();
+
+        let ping = ofs.get_file("/bin/ping").unwrap();
+        let first = ping.metadata.as_ref().unwrap();
+        assert_eq!(first.mode, 0o4755);
+        assert_eq!(first.mtime, 1_600_000_000);
+        assert_eq!(first.xattrs["security.capability"], capability);
+        let latest = ping.versions[0].metadata.as_ref().unwrap();
+        assert_eq!((latest.mode, latest.uid, latest.gid), (0o755, 1000, 1000));
+        assert!(latest.xattrs.is_empty());
+        // chmod stores the same bytes again
+        assert_eq!(latest.digest, first.digest);
+        assert_eq!(
+            latest.digest.as_deref(),
+            Some(sha256_digest(b"ping").as_str())
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ofs.cbor");
+        crate::cache::save(&path, "sha256:test", &ofs).unwrap();
+        let ofs: OverlayFs = crate::cache::load(&path, "sha256:test").unwrap().unwrap();
+        let ping = ofs.get_file("/bin/ping").unwrap();
+        assert_eq!(ping.metadata.as_ref().unwrap().xattrs.len(), 1);
+        assert_eq!(ping.versions[0].metadata.as_ref().unwrap().uid, 1000);
     }
-    None
 }
@@ -1,7 +1,9 @@
+use super::oci::{is_oci_store, OciLayout, Platform};
+use super::store::ImageStore;
+use crate::error::{Error, Result};
 use serde::{Deserialize, Serialize};
-use std::fs::File;
-use std::io::Error;
-use std::io::Read;
+
+const MANIFEST_FILE: &str = "manifest.json";
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
@@ -9,34 +11,72 @@ pub struct Manifest {
     pub config: String,
     pub repo_tags: Option<Vec<String>>,
     pub layers: Vec<String>,
+    // Only known for images read from an OCI index
+    #[serde(skip)]
+    pub platform: Option<Platform>,
 }
 
 impl Manifest {
-    pub fn from(string: &str) -> Manifest {
-        let manifests: Vec<Manifest> = serde_json::from_str(string).unwrap();
-        manifests[0].to_owned()
+    pub fn all(string: &str) -> Result<Vec<Manifest>> {
+        serde_json::from_str(string).map_err(|e| Error::corrupt("Invalid manifest.json", e))
+    }
+
+    pub fn for_store(store: &dyn ImageStore) -> Result<Manifest> {
+        Manifest::list(store)?
+            .into_iter()
+            .next()
+            .ok_or_else(|| Error::CorruptArchive("No image found in archive".to_string()))
+    }
+
+    // docker save writes a manifest.json, OCI archives only an index.json.
+    // Both list every image when more than one was saved.
+    pub fn list(store: &dyn ImageStore) -> Result<Vec<Manifest>> {
+        if !store.exists(MANIFEST_FILE) && is_oci_store(store) {
+            return OciLayout::new(store).manifests();
+        }
+
+        let json = store.read_to_string(MANIFEST_FILE).map_err(|e| {
+            Error::corrupt("Not an image archive, no manifest.json or index.json", e)
+        })?;
+        Manifest::all(&json)
+    }
+
+    // The config blob is named after its digest, <hex>.json or blobs/sha256/<hex>
+    pub fn config_digest(&self) -> String {
+        let name = self.config.rsplit('/').next().unwrap_or(&self.config);
+        format!("sha256:{}", name.trim_end_matches(".json"))
     }
 
-    pub fn for_image_path(image_id: &str) -> Result<Manifest, Error> {
-        let manifest_path = format!("{}/manifest.json", image_id);
-        let mut input = File::open(manifest_path)?;
+    pub fn name(&self) -> String {
+        match self.repo_tags.as_ref().and_then(|tags| tags.first()) {
+            Some(tag) => tag.clone(),
+            None => self.config_digest().chars().take(19).collect(),
+        }
+    }
 
-        let mut json = String::new();
-        input.read_to_string(&mut json)?;
+    // Matches a repo tag (latest when no tag is given) or a prefix of the config digest
+    pub fn matches(&self, selector: &str) -> bool {
+        let tags = self.repo_tags.as_deref().unwrap_or(&[]);
+        let latest = format!("{}:latest", selector);
+        if tags.iter().any(|t| t == selector || *t == latest) {
+            return true;
+        }
 
-        Ok(Manifest::from(&json))
+        let hex = selector.strip_prefix("sha256:").unwrap_or(selector);
+        !hex.is_empty() && self.config_digest()["sha256:".len()..].starts_with(hex)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::image::manifest::Manifest;
+    use crate::image::store::DirStore;
 
     #[test]
     fn deserialize_manifest_string() {
         let str_manifest = "[{\"Config\":\"6dbb9cc54074106d46d4ccb330f2a40a682d49dda5f4844962b7dce9fe44aaec.json\",\"RepoTags\":null,\"Layers\":[\"2309bd4f07083b22f40dfc9a1241015274e232cb15afccc17dac0ece450d70e7/layer.tar\"]}]".to_string();
 
-        let manifest = Manifest::from(&str_manifest);
+        let manifest = Manifest::all(&str_manifest).unwrap().remove(0);
         assert_eq!(
             manifest.config,
             "6dbb9cc54074106d46d4ccb330f2a40a682d49dda5f4844962b7dce9fe44aaec.json"
@@ -46,10 +86,32 @@ mod tests {
     #[test]
     fn read_from_path() {
         let path = "test_files";
-        let manifest = Manifest::for_image_path(path).unwrap();
+        let manifest = Manifest::for_store(&DirStore::new(path)).unwrap();
         assert_eq!(
             manifest.config,
             "6dbb9cc54074106d46d4ccb330f2a40a682d49dda5f4844962b7dce9fe44aaec.json"
         );
     }
+
+    #[test]
+    fn selects_image_by_tag_or_digest() {
+        let json = r#"[{"Config":"aaaa1111.json","RepoTags":["app:1.0","app:latest"],"Layers":[]},{"Config":"bbbb2222.json","RepoTags":null,"Layers":[]}]"#;
+        let manifests = Manifest::all(json).unwrap();
+        assert_eq!(manifests.len(), 2);
+
+        assert!(manifests[0].matches("app"));
+        assert!(manifests[0].matches("app:1.0"));
+        assert!(!manifests[0].matches("app:2.0"));
+        assert!(manifests[1].matches("sha256:bbbb"));
+        assert!(manifests[1].matches("bbbb2222"));
+        assert!(!manifests[1].matches(""));
+        assert_eq!(manifests[1].name(), "sha256:bbbb2222");
+    }
+
+    #[test]
+    fn read_from_oci_layout() {
+        let manifest = Manifest::for_store(&DirStore::new("test_files/oci")).unwrap();
+        assert_eq!(manifest.layers.len(), 2);
+        assert!(manifest.config.starts_with("blobs/sha256/"));
+    }
 }
@@ -1,3 +1,9 @@
+pub mod archive;
+pub mod config;
+pub mod digest;
 pub mod image_tar;
 pub mod inspect;
 pub mod manifest;
+pub mod oci;
+pub mod rootfs;
+pub mod store;
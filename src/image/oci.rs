@@ -0,0 +1,311 @@
+// Reader for OCI image layouts, https://github.com/opencontainers/image-spec/blob/main/image-layout.md
+use super::manifest::Manifest;
+use super::store::ImageStore;
+use crate::error::{Error, Result};
+use log::trace;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+
+pub const OCI_LAYOUT_FILE: &str = "oci-layout";
+pub const INDEX_FILE: &str = "index.json";
+
+const REF_NAME_ANNOTATION: &str = "org.opencontainers.image.ref.name";
+const CONTAINERD_NAME_ANNOTATION: &str = "io.containerd.image.name";
+
+pub const OCI_INDEX: &str = "application/vnd.oci.image.index.v1+json";
+pub const OCI_MANIFEST: &str = "application/vnd.oci.image.manifest.v1+json";
+pub const DOCKER_MANIFEST_LIST: &str = "application/vnd.docker.distribution.manifest.list.v2+json";
+
+// buildx stores provenance attestations as manifests for this platform
+const UNKNOWN: &str = "unknown";
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Platform {
+    pub architecture: String,
+    pub os: String,
+    pub variant: Option<String>,
+}
+
+impl Platform {
+    // Parses os/arch[/variant], as used by `docker --platform`
+    pub fn parse(platform: &str) -> std::result::Result<Platform, String> {
+        let parts: Vec<&str> = platform.split('/').collect();
+        match parts.as_slice() {
+            [os, arch] | [os, arch, _] if !os.is_empty() && !arch.is_empty() => Ok(Platform {
+                os: os.to_string(),
+                architecture: arch.to_string(),
+                variant: parts.get(2).map(|v| v.to_string()),
+            }),
+            _ => Err(format!(
+                "Invalid platform {}, expected os/arch[/variant]",
+                platform
+            )),
+        }
+    }
+
+    pub fn host() -> Platform {
+        Platform {
+            os: "linux".to_string(),
+            architecture: host_architecture().to_string(),
+            variant: None,
+        }
+    }
+
+    // A platform without a variant matches every variant of its architecture
+    pub fn matches(&self, wanted: &Platform) -> bool {
+        self.os == wanted.os
+            && self.architecture == wanted.architecture
+            && (wanted.variant.is_none() || self.variant == wanted.variant)
+    }
+
+    pub fn is_unknown(&self) -> bool {
+        self.os == UNKNOWN || self.architecture == UNKNOWN
+    }
+}
+
+impl fmt::Display for Platform {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.os, self.architecture)?;
+        if let Some(variant) = &self.variant {
+            write!(f, "/{}", variant)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Descriptor {
+    pub media_type: String,
+    pub digest: String,
+    pub size: u64,
+    pub annotations: Option<HashMap<String, String>>,
+    pub platform: Option<Platform>,
+}
+
+pub fn is_index_media_type(media_type: &str) -> bool {
+    media_type == OCI_INDEX || media_type == DOCKER_MANIFEST_LIST
+}
+
+// Maps the rust target arch to the GOARCH names used in image platforms
+pub fn host_architecture() -> &'static str {
+    match std::env::consts::ARCH {
+        "x86_64" => "amd64",
+        "aarch64" => "arm64",
+        "x86" => "386",
+        "powerpc64" => "ppc64le",
+        arch => arch,
+    }
+}
+
+impl Descriptor {
+    pub fn is_index(&self) -> bool {
+        is_index_media_type(&self.media_type)
+    }
+
+    pub fn ref_name(&self) -> Option<String> {
+        let annotations = self.annotations.as_ref()?;
+        annotations
+            .get(CONTAINERD_NAME_ANNOTATION)
+            .or_else(|| annotations.get(REF_NAME_ANNOTATION))
+            .cloned()
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImageIndex {
+    pub schema_version: u32,
+    pub media_type: Option<String>,
+    pub manifests: Vec<Descriptor>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImageManifest {
+    pub schema_version: u32,
+    pub media_type: Option<String>,
+    pub config: Descriptor,
+    pub layers: Vec<Descriptor>,
+}
+
+impl ImageIndex {
+    // Picks the manifest for the host platform, or the first one when there is none
+    pub fn default_manifest(&self) -> Option<&Descriptor> {
+        self.manifest_for(&Platform::host())
+            .or_else(|| self.manifests.first())
+    }
+
+    pub fn manifest_for(&self, platform: &Platform) -> Option<&Descriptor> {
+        self.manifests.iter().find(|d| match &d.platform {
+            Some(p) => p.matches(platform),
+            None => false,
+        })
+    }
+
+    pub fn platforms(&self) -> Vec<Platform> {
+        self.manifests
+            .iter()
+            .filter_map(|d| d.platform.clone())
+            .filter(|p| !p.is_unknown())
+            .collect()
+    }
+}
+
+// Blobs are stored content addressable as blobs/<alg>/<encoded>
+pub fn blob_path(digest: &str) -> Result<String> {
+    match digest.split_once(':') {
+        Some((alg, encoded)) if !alg.is_empty() && !encoded.is_empty() => {
+            Ok(format!("blobs/{}/{}", alg, encoded))
+        }
+        _ => Err(Error::CorruptArchive(format!("Invalid digest: {}", digest))),
+    }
+}
+
+pub fn is_oci_store(store: &dyn ImageStore) -> bool {
+    store.exists(OCI_LAYOUT_FILE) && store.exists(INDEX_FILE)
+}
+
+// The digest of a blobs/<alg>/<encoded> path, None for other paths
+pub fn blob_digest(path: &str) -> Option<String> {
+    let parts: Vec<&str> = path.trim_start_matches("./").split('/').collect();
+    match parts.as_slice() {
+        ["blobs", alg, encoded] if !alg.is_empty() && !encoded.is_empty() => {
+            Some(format!("{}:{}", alg, encoded))
+        }
+        _ => None,
+    }
+}
+
+fn read_json<T: serde::de::DeserializeOwned>(store: &dyn ImageStore, name: &str) -> Result<T> {
+    let json = store
+        .read_to_string(name)
+        .map_err(|e| Error::corrupt(&format!("Failed to read {}", name), e))?;
+
+    serde_json::from_str(&json).map_err(|e| Error::corrupt(&format!("Failed to parse {}", name), e))
+}
+
+pub struct OciLayout<'a> {
+    store: &'a dyn ImageStore,
+}
+
+impl<'a> OciLayout<'a> {
+    pub fn new(store: &'a dyn ImageStore) -> OciLayout<'a> {
+        OciLayout { store }
+    }
+
+    pub fn read_index(&self) -> Result<ImageIndex> {
+        read_json(self.store, INDEX_FILE)
+    }
+
+    fn read_blob<T: serde::de::DeserializeOwned>(&self, digest: &str) -> Result<T> {
+        read_json(self.store, &blob_path(digest)?)
+    }
+
+    // Walks index -> (nested index) -> manifest and returns one Manifest per image
+    pub fn manifests(&self) -> Result<Vec<Manifest>> {
+        let index = self.read_index()?;
+        let mut manifests = vec![];
+        for desc in index.manifests.iter() {
+            self.resolve(desc, desc.ref_name(), desc.platform.clone(), &mut manifests)?;
+        }
+        Ok(manifests)
+    }
+
+    fn resolve(
+        &self,
+        desc: &Descriptor,
+        name: Option<String>,
+        platform: Option<Platform>,
+        manifests: &mut Vec<Manifest>,
+    ) -> Result<()> {
+        if desc.is_index() {
+            trace!("Resolving nested index {}", desc.digest);
+            let index: ImageIndex = self.read_blob(&desc.digest)?;
+            for d in index.manifests.iter() {
+                let platform = d.platform.clone().or_else(|| platform.clone());
+                self.resolve(
+                    d,
+                    d.ref_name().or_else(|| name.clone()),
+                    platform,
+                    manifests,
+                )?;
+            }
+            return Ok(());
+        }
+
+        trace!("Reading image manifest {}", desc.digest);
+        let image: ImageManifest = self.read_blob(&desc.digest)?;
+        let mut layers = vec![];
+        for l in image.layers.iter() {
+            layers.push(blob_path(&l.digest)?);
+        }
+
+        manifests.push(Manifest {
+            config: blob_path(&image.config.digest)?,
+            repo_tags: name.map(|n| vec![n]),
+            layers,
+            platform,
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::image::oci::{blob_digest, blob_path, is_oci_store, OciLayout, Platform};
+    use crate::image::store::DirStore;
+
+    #[test]
+    fn converts_digest_to_blob_path() {
+        assert_eq!(
+            blob_path("sha256:abcd").unwrap(),
+            "blobs/sha256/abcd".to_string()
+        );
+        assert!(blob_path("abcd").is_err());
+        assert_eq!(
+            blob_digest("blobs/sha256/abcd"),
+            Some("sha256:abcd".to_string())
+        );
+        assert_eq!(blob_digest("abcd/layer.tar"), None);
+    }
+
+    #[test]
+    fn parses_and_matches_platforms() {
+        let arm = Platform::parse("linux/arm64/v8").unwrap();
+        assert_eq!(arm.to_string(), "linux/arm64/v8");
+        assert!(arm.matches(&Platform::parse("linux/arm64").unwrap()));
+        assert!(!arm.matches(&Platform::parse("linux/arm64/v7").unwrap()));
+        assert!(!arm.matches(&Platform::parse("linux/amd64").unwrap()));
+        assert!(Platform::parse("linux").is_err());
+        assert!(Platform::parse("unknown/unknown").unwrap().is_unknown());
+    }
+
+    #[test]
+    fn resolves_layers_from_index() {
+        let path = "test_files/oci";
+        let store = DirStore::new(path);
+        assert!(is_oci_store(&store));
+
+        let manifests = OciLayout::new(&store).manifests().unwrap();
+        assert_eq!(manifests.len(), 1);
+        assert_eq!(
+            manifests[0].config,
+            "blobs/sha256/15c57637dc26ff3b3502da721493bdd947c472d3e3f3857753d482557641ccff"
+        );
+        assert_eq!(
+            manifests[0].repo_tags,
+            Some(vec!["cake-test:latest".to_string()])
+        );
+        assert_eq!(manifests[0].layers.len(), 2);
+        assert_eq!(
+            manifests[0].platform,
+            Some(Platform::parse("linux/amd64").unwrap())
+        );
+        assert_eq!(
+            manifests[0].layers[0],
+            "blobs/sha256/9d88a2580b57ae893043614360747707059c6ec8278753f2177bb9616bc5a202"
+        );
+    }
+}
@@ -0,0 +1,264 @@
+// Root filesystems which are not images: chroot build directories, unpacked
+// VM trees and flat tarballs like the ones `docker export` writes.
+use super::archive::is_compressed_file;
+use super::digest::HashingReader;
+use super::image_tar::{decompress, LayerEntry};
+use super::oci::INDEX_FILE;
+use crate::error::{Context, Result};
+use crate::ofs::file_info::Metadata;
+use log::{trace, warn};
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File};
+use std::io::{self, ErrorKind, Read};
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+use tar::{Archive, Entries};
+
+const MANIFEST_FILE: &str = "manifest.json";
+
+// Anything without an image manifest is taken as a root filesystem
+pub fn is_rootfs(path: &Path) -> bool {
+    if path.is_dir() {
+        return !path.join(MANIFEST_FILE).exists() && !path.join(INDEX_FILE).exists();
+    }
+
+    // Only the headers up to the first telling path are read, the layers of a
+    // plain tar are seeked over
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(_) => return false,
+    };
+    let rootfs = if is_compressed_file(path) {
+        decompress(file).and_then(|r| Archive::new(r).entries().and_then(has_no_manifest))
+    } else {
+        Archive::new(file)
+            .entries_with_seek()
+            .and_then(has_no_manifest)
+    };
+    rootfs.unwrap_or(false)
+}
+
+// What `docker save` and OCI layouts keep next to the manifest: blobs, and
+// directories and configs named by a digest
+fn is_image_path(name: &str) -> bool {
+    let top = name.split('/').next().unwrap_or_default();
+    let id = top.strip_suffix(".json").unwrap_or(top);
+    matches!(top, "blobs" | "oci-layout" | "repositories")
+        || (id.len() == 64 && id.bytes().all(|b| b.is_ascii_hexdigit()))
+}
+
+fn has_no_manifest<R: Read>(entries: Entries<R>) -> io::Result<bool> {
+    for e in entries {
+        let e = e?;
+        let path = e.path()?;
+        let name = path.to_string_lossy();
+        let name = name.trim_start_matches("./").trim_end_matches('/');
+        if name == MANIFEST_FILE || name == INDEX_FILE {
+            return Ok(false);
+        }
+        if !name.is_empty() && !is_image_path(name) {
+            return Ok(true);
+        }
+    }
+    Ok(true)
+}
+
+// The same fields a tar header has, extended attributes are not read
+fn metadata(meta: &fs::Metadata) -> Metadata {
+    Metadata {
+        mode: meta.mode() & 0o7777,
+        uid: meta.uid().into(),
+        gid: meta.gid().into(),
+        mtime: meta.mtime().max(0) as u64,
+        xattrs: BTreeMap::new(),
+        digest: None,
+    }
+}
+
+fn hash_file(path: &Path) -> io::Result<String> {
+    HashingReader::new(File::open(path)?).finish()
+}
+
+// Walks the tree like a single layer, files which can not be read are left out.
+// Files sharing an inode become hardlinks to the first one found.
+pub fn walk_dir(root: &Path, hash: bool) -> Result<Vec<LayerEntry>> {
+    let mut entries = vec![];
+    let mut dirs = vec![String::new()];
+    let mut inodes: HashMap<(u64, u64), String> = HashMap::new();
+
+    while let Some(dir) = dirs.pop() {
+        let listing = match fs::read_dir(root.join(&dir)) {
+            Ok(listing) => listing,
+            Err(e) if dir.is_empty() => return Err(e).context(root.display()),
+            Err(e) => {
+                warn!("Skipping {}: {}", dir, e);
+                continue;
+            }
+        };
+
+        for entry in listing {
+            let entry = entry.context(root.join(&dir).display())?;
+            let path = format!("{}{}", dir, entry.file_name().to_string_lossy());
+            // Symlinks are not followed, they may point out of the tree
+            let meta = match entry.path().symlink_metadata() {
+                Ok(meta) => meta,
+                Err(e) => {
+                    warn!("Skipping {}: {}", path, e);
+                    continue;
+                }
+            };
+
+            let mut metadata = metadata(&meta);
+            if hash && meta.is_file() {
+                match hash_file(&entry.path()) {
+                    Ok(digest) => metadata.digest = Some(digest),
+                    Err(e) => warn!("Can not hash {}: {}", path, e),
+                }
+            }
+            if meta.is_dir() {
+                let path = format!("{}/", path);
+                entries.push(LayerEntry::Dir(path.clone(), metadata));
+                dirs.push(path);
+            } else if meta.file_type().is_symlink() {
+                match fs::read_link(entry.path()) {
+                    Ok(target) => entries.push(LayerEntry::Symlink(
+                        path,
+                        target.to_string_lossy().into_owned(),
+                        metadata,
+                    )),
+                    Err(e) => warn!("Skipping {}: {}", path, e),
+                }
+            } else if meta.is_file() && meta.nlink() > 1 {
+                match inodes.entry((meta.dev(), meta.ino())) {
+                    Entry::Occupied(first) => {
+                        entries.push(LayerEntry::Hardlink(path, first.get().clone(), metadata))
+                    }
+                    Entry::Vacant(first) => {
+                        first.insert(path.clone());
+                        entries.push(LayerEntry::File(path, meta.len(), metadata));
+                    }
+                }
+            } else if meta.is_file() {
+                entries.push(LayerEntry::File(path, meta.len(), metadata));
+            }
+        }
+    }
+    trace!("Found {} entries in {}", entries.len(), root.display());
+    Ok(entries)
+}
+
+pub fn read_file(root: &Path, path: &str) -> Result<Option<String>> {
+    let path = root.join(path.trim_start_matches('/'));
+    match fs::read(&path) {
+        Ok(content) => Ok(Some(String::from_utf8_lossy(&content).into_owned())),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e).context(path.display()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::image::image_tar::{apply_layer, LayerEntry};
+    use crate::image::rootfs::{is_rootfs, read_file, walk_dir};
+    use crate::ofs::ofs::OverlayFs;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::fs;
+    use std::io::Write;
+    use std::os::unix::fs::PermissionsExt;
+    use std::path::Path;
+    use tar::{Builder, Header};
+
+    fn tar(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut builder = Builder::new(vec![]);
+        for (name, content) in files {
+            let mut header = Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            builder.append_data(&mut header, name, *content).unwrap();
+        }
+        builder.into_inner().unwrap()
+    }
+
+    #[test]
+    fn walks_root_filesystem_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("lib/apk/db")).unwrap();
+        fs::create_dir_all(root.join("tmp")).unwrap();
+        fs::write(root.join("lib/apk/db/installed"), "P:musl\nV:1.2\n").unwrap();
+        fs::set_permissions(
+            root.join("lib/apk/db/installed"),
+            fs::Permissions::from_mode(0o644),
+        )
+        .unwrap();
+        fs::write(root.join("etc"), "not a dir").unwrap();
+        std::os::unix::fs::symlink("lib/apk", root.join("apk")).unwrap();
+        fs::hard_link(
+            root.join("lib/apk/db/installed"),
+            root.join("tmp/installed"),
+        )
+        .unwrap();
+        assert!(is_rootfs(root));
+        assert!(!is_rootfs(std::path::Path::new("test_files/oci")));
+
+        let entries = walk_dir(root, false).unwrap();
+        assert!(entries.iter().any(
+            |e| matches!(e, LayerEntry::File(p, 13, m) if p == "lib/apk/db/installed" && m.mode == 0o644)
+        ));
+        assert!(entries
+            .iter()
+            .any(|e| matches!(e, LayerEntry::Dir(p, _) if p == "tmp/")));
+        assert!(entries
+            .iter()
+            .any(|e| matches!(e, LayerEntry::Symlink(p, t, _) if p == "apk" && t == "lib/apk")));
+        assert_eq!(
+            entries
+                .iter()
+                .filter(|e| matches!(e, LayerEntry::Hardlink(..)))
+                .count(),
+            1
+        );
+
+        let mut ofs = OverlayFs::new();
+        apply_layer(&mut ofs, &entries, "layer0").unwrap();
+        ofs.update_sizes();
+        assert_eq!(ofs.get_file("/etc").unwrap().size, 9);
+        assert_eq!(ofs.size(), 22);
+        assert_eq!(
+            read_file(root, "/lib/apk/db/installed").unwrap().unwrap(),
+            "P:musl\nV:1.2\n"
+        );
+        assert!(read_file(root, "/usr/lib/os-release").unwrap().is_none());
+    }
+
+    // Legacy `docker save` writes manifest.json after the layers
+    #[test]
+    fn decides_from_the_first_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let id = "a".repeat(64);
+        let layer = format!("{}/layer.tar", id);
+        let config = format!("{}.json", id);
+        let saved = tar(&[
+            (&layer, b"layer"),
+            (&config, b"{}"),
+            ("manifest.json", b"[]"),
+        ]);
+        fs::write(dir.path().join("app.tar"), &saved).unwrap();
+        assert!(!is_rootfs(&dir.path().join("app.tar")));
+
+        let mut gz = GzEncoder::new(vec![], Compression::default());
+        gz.write_all(&saved).unwrap();
+        fs::write(dir.path().join("app.tar.gz"), gz.finish().unwrap()).unwrap();
+        assert!(!is_rootfs(&dir.path().join("app.tar.gz")));
+
+        // Nothing after the first file of a `docker export` is read
+        let mut exported = tar(&[("bin/sh", b"#!")]);
+        exported.truncate(1024);
+        exported.extend_from_slice(&[0xff; 512]);
+        fs::write(dir.path().join("rootfs.tar"), &exported).unwrap();
+        assert!(is_rootfs(&dir.path().join("rootfs.tar")));
+        assert!(!is_rootfs(Path::new("test_files/missing.tar")));
+    }
+}
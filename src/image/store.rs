@@ -0,0 +1,46 @@
+// Images are read either from a directory (OCI layout, extracted `docker save`)
+// or straight from the archive, without extracting it.
+use crate::ofs::layer::Layer;
+use std::fs::File;
+use std::io::{Read, Result};
+use std::path::Path;
+
+pub trait ImageStore {
+    fn exists(&self, name: &str) -> bool;
+    fn open(&self, name: &str) -> Result<Box<dyn Read + '_>>;
+    fn layer(&self, index: usize, name: &str) -> Result<Layer>;
+
+    fn read_to_string(&self, name: &str) -> Result<String> {
+        let mut content = String::new();
+        self.open(name)?.read_to_string(&mut content)?;
+        Ok(content)
+    }
+}
+
+pub struct DirStore<'a> {
+    dir: &'a str,
+}
+
+impl<'a> DirStore<'a> {
+    pub fn new(dir: &'a str) -> DirStore<'a> {
+        DirStore { dir }
+    }
+
+    fn path(&self, name: &str) -> String {
+        format!("{}/{}", self.dir, name)
+    }
+}
+
+impl<'a> ImageStore for DirStore<'a> {
+    fn exists(&self, name: &str) -> bool {
+        Path::new(&self.path(name)).exists()
+    }
+
+    fn open(&self, name: &str) -> Result<Box<dyn Read + '_>> {
+        Ok(Box::new(File::open(self.path(name))?))
+    }
+
+    fn layer(&self, index: usize, name: &str) -> Result<Layer> {
+        Ok(Layer::new(index, self.path(name)))
+    }
+}
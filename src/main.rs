@@ -1,24 +1,38 @@
-use clap::{App, Arg};
-use log::trace;
+use clap::{App, AppSettings, Arg, ArgMatches};
+use docker::source;
+use error::Context;
+use image::oci::Platform;
+use log::{error, trace};
+use ofs::directory::{TreeOptions, TreeSort};
+use ofs::utils::parse_size;
+use std::process;
+use workspace::{Workspace, SECONDS_PER_DAY};
 
 mod analysis;
 mod analyze;
+mod cache;
 mod docker;
+mod error;
 mod image;
 mod logs;
 mod ofs;
 mod packages;
+mod registry;
 mod style;
+mod tui;
+mod workspace;
 
 fn main() {
     let matches = App::new("cake")
         .version(clap::crate_version!())
+        .setting(AppSettings::SubcommandsNegateReqs)
         .arg(
             Arg::new("loglevel")
                 .short('l')
                 .long("loglevel")
                 .value_name("LEVEL")
                 .possible_values(&["error", "warn", "info", "debug", "trace"])
+                .global(true)
                 .takes_value(true),
         )
         .arg(
@@ -26,29 +40,333 @@ fn main() {
                 .about("the input image to use")
                 .required(true),
         )
+        .args(analysis_args())
         .arg(
-            Arg::new("packages")
-                .about("the list of packages installed")
-                .short('p')
-                .long("packages")
+            Arg::new("select")
+                .about("the image to analyze in an archive with several images, by tag or digest")
+                .long("select")
+                .value_name("TAG|DIGEST")
+                .conflicts_with("all")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("all")
+                .about("analyze every image in the archive")
+                .long("all")
                 .takes_value(false),
         )
         .arg(
-            Arg::new("tree")
-                .short('t')
-                .long("tree")
-                .hidden(true)
+            Arg::new("list")
+                .about("list the images in the archive")
+                .long("list")
                 .takes_value(false),
         )
+        .arg(
+            Arg::new("platform")
+                .about("the platform to analyze for multi-arch images, e.g. linux/arm64")
+                .long("platform")
+                .value_name("OS/ARCH[/VARIANT]")
+                .conflicts_with("all-platforms")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("all-platforms")
+                .about("analyze every platform of a multi-arch image and compare them")
+                .long("all-platforms")
+                .takes_value(false),
+        )
+        .subcommand(
+            App::new("container")
+                .about("analyze what a running or stopped container wrote on top of its image")
+                .arg(
+                    Arg::new("ID")
+                        .about("the id or name of the container")
+                        .required(true),
+                )
+                .args(analysis_args()),
+        )
+        .subcommand(
+            App::new("layers")
+                .about("list the layers of an image with what each one added, modified and deleted")
+                .arg(
+                    Arg::new("IMAGE")
+                        .about("the input image to use")
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("layer")
+                        .about("print the files the layer changed, like docker diff")
+                        .long("layer")
+                        .value_name("N")
+                        .takes_value(true),
+                )
+                .args(analysis_args()),
+        )
+        .subcommand(
+            App::new("tree")
+                .about("print the files of an image as a tree, sorted and cut to size")
+                .arg(
+                    Arg::new("IMAGE")
+                        .about("the input image to use")
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("PATH")
+                        .about("the directory to start from")
+                        .default_value("/"),
+                )
+                .arg(
+                    Arg::new("depth")
+                        .about("how many levels of directories to print")
+                        .long("depth")
+                        .value_name("N")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::new("sort")
+                        .about("the order of the entries of a directory")
+                        .long("sort")
+                        .possible_values(&["name", "size"])
+                        .default_value("name")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::new("min-size")
+                        .about("sum up smaller entries in one line, e.g. 1MB")
+                        .long("min-size")
+                        .value_name("SIZE")
+                        .takes_value(true),
+                )
+                .args(analysis_args()),
+        )
+        .subcommand(
+            App::new("cache")
+                .about("manage the cache of analyzed images")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(App::new("list").about("list the cached images"))
+                .subcommand(
+                    App::new("prune")
+                        .about("remove images not analyzed in the last DAYS days")
+                        .arg(
+                            Arg::new("days")
+                                .long("days")
+                                .value_name("DAYS")
+                                .default_value("30")
+                                .takes_value(true),
+                        ),
+                )
+                .subcommand(App::new("clear").about("remove everything from the cache")),
+        )
         .get_matches();
 
     logs::setup_logging(&matches);
 
+    if let Some(cache) = matches.subcommand_matches("cache") {
+        let workspace = Workspace::new();
+        match cache.subcommand() {
+            Some(("list", _)) => workspace.show_entries(),
+            Some(("prune", prune)) => {
+                let days: u64 = prune.value_of_t_or_exit("days");
+                let removed = workspace.prune(days * SECONDS_PER_DAY);
+                println!("Removed {} cached images", removed.len());
+            }
+            Some(("clear", _)) => workspace.clear(),
+            _ => unreachable!(),
+        }
+        return;
+    }
+
+    if let Some(container) = matches.subcommand_matches("container") {
+        let workspace = workspace(container);
+        let result = analyze::analyze_container(
+            container.value_of("ID").unwrap(),
+            container.value_of("source").unwrap(),
+            &workspace,
+            &options(container),
+        );
+        drop(workspace);
+        exit_on_error(result);
+        return;
+    }
+
+    if let Some(layers) = matches.subcommand_matches("layers") {
+        let view = match layers.value_of("layer") {
+            Some(_) => analyze::LayerView::Changes(layers.value_of_t_or_exit("layer")),
+            None => analyze::LayerView::All,
+        };
+        let workspace = workspace(layers);
+        let result = analyze::analyze_image(
+            layers.value_of("IMAGE").unwrap(),
+            layers.value_of("source").unwrap(),
+            &workspace,
+            &analyze::Selection::First,
+            &analyze::Platforms::Default,
+            &analyze::Options {
+                layers: Some(view),
+                ..options(layers)
+            },
+        );
+        drop(workspace);
+        exit_on_error(result);
+        return;
+    }
+
+    if let Some(tree) = matches.subcommand_matches("tree") {
+        let min_size = match tree.value_of("min-size").map(parse_size) {
+            Some(Some(size)) => size,
+            Some(None) => clap::Error::with_description(
+                "Invalid value for '--min-size <SIZE>': expected a size like 500KB or 1.5MB"
+                    .to_string(),
+                clap::ErrorKind::InvalidValue,
+            )
+            .exit(),
+            None => 0,
+        };
+        let tree_options = TreeOptions {
+            depth: tree
+                .is_present("depth")
+                .then(|| tree.value_of_t_or_exit("depth")),
+            sort: match tree.value_of("sort") {
+                Some("size") => TreeSort::Size,
+                _ => TreeSort::Name,
+            },
+            min_size,
+        };
+        let workspace = workspace(tree);
+        let result = analyze::analyze_image(
+            tree.value_of("IMAGE").unwrap(),
+            tree.value_of("source").unwrap(),
+            &workspace,
+            &analyze::Selection::First,
+            &analyze::Platforms::Default,
+            &analyze::Options {
+                tree: Some(analyze::TreeView {
+                    path: tree.value_of("PATH").unwrap().to_string(),
+                    options: tree_options,
+                }),
+                ..options(tree)
+            },
+        );
+        drop(workspace);
+        exit_on_error(result);
+        return;
+    }
+
     let image = matches.value_of("IMAGE").unwrap();
     trace!("Using IMAGE file: {}", image);
 
-    let pkgs = matches.is_present("packages");
-    let tree = matches.is_present("tree");
+    if matches.is_present("list") {
+        exit_on_error(analyze::list_images(image));
+        return;
+    }
+
+    let source = matches.value_of("source").unwrap();
+
+    let workspace = workspace(&matches);
+
+    let selection = match matches.value_of("select") {
+        Some(selector) => analyze::Selection::Image(selector),
+        None if matches.is_present("all") => analyze::Selection::All,
+        None => analyze::Selection::First,
+    };
+
+    let platforms = match matches.value_of("platform") {
+        Some(platform) => match Platform::parse(platform) {
+            Ok(platform) => analyze::Platforms::Only(platform),
+            Err(e) => clap::Error::with_description(e, clap::ErrorKind::InvalidValue).exit(),
+        },
+        None if matches.is_present("all-platforms") => analyze::Platforms::All,
+        None => analyze::Platforms::Default,
+    };
+
+    let result = analyze::analyze_image(
+        image,
+        source,
+        &workspace,
+        &selection,
+        &platforms,
+        &options(&matches),
+    );
+    // process::exit skips destructors, remove a temporary workspace first
+    drop(workspace);
+    exit_on_error(result);
+}
+
+// Options shared by the image and container analysis
+fn analysis_args() -> Vec<Arg<'static>> {
+    vec![
+        Arg::new("source")
+            .about("where to get the image from, auto picks the first container runtime found")
+            .short('s')
+            .long("source")
+            .value_name("SOURCE")
+            .possible_values(&[
+                source::AUTO,
+                source::DOCKER,
+                source::DOCKER_API,
+                source::PODMAN,
+                source::NERDCTL,
+                source::CTR,
+                analyze::REGISTRY_SOURCE,
+            ])
+            .default_value(source::AUTO)
+            .takes_value(true),
+        Arg::new("packages")
+            .about("the list of packages installed")
+            .short('p')
+            .long("packages")
+            .takes_value(false),
+        Arg::new("interactive")
+            .about("explore the layers and files of the image in the terminal")
+            .short('i')
+            .long("interactive")
+            .takes_value(false),
+        Arg::new("tree")
+            .short('t')
+            .long("tree")
+            .hidden(true)
+            .takes_value(false),
+        Arg::new("hash")
+            .about("hash the content of every file to find duplicates, reads the whole image")
+            .long("hash")
+            .takes_value(false),
+        Arg::new("no-cache")
+            .about("do not read or write the cache, work in a temporary directory")
+            .long("no-cache")
+            .takes_value(false),
+    ]
+}
+
+fn options(matches: &ArgMatches) -> analyze::Options {
+    analyze::Options {
+        pkgs: matches.is_present("packages"),
+        tree: matches.is_present("tree").then(|| analyze::TreeView {
+            path: "/".to_string(),
+            options: TreeOptions::default(),
+        }),
+        interactive: matches.is_present("interactive"),
+        hash: matches.is_present("hash"),
+        layers: None,
+    }
+}
+
+fn workspace(matches: &ArgMatches) -> Workspace {
+    if matches.is_present("no-cache") {
+        match Workspace::temporary().context("Failed to create a temporary directory") {
+            Ok(workspace) => workspace,
+            Err(e) => {
+                error!("{}", e);
+                process::exit(e.exit_code());
+            }
+        }
+    } else {
+        Workspace::new()
+    }
+}
 
-    analyze::analyze_image(image, pkgs, tree);
+fn exit_on_error(result: error::Result<()>) {
+    if let Err(e) = result {
+        error!("{}", e);
+        process::exit(e.exit_code());
+    }
 }
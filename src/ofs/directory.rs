@@ -1,232 +1,417 @@
-use crate::ofs::file_info::FileInfo;
-use crate::ofs::utils::{
-    get_leading_entry, get_remaining, size_human, split_last_entry, BRANCH_SPACE, MIDDLE_ITEM,
-};
-use log::trace;
+use crate::ofs::file_info::{FileEntry, Metadata};
+use crate::ofs::names::NameId;
+use crate::ofs::ofs::{NodeId, OverlayFs, NO_NODE};
+use crate::ofs::utils::{size_human, BRANCH_SPACE, LAST_ITEM, MIDDLE_ITEM, NO_BRANCH_SPACE};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
 
+// A directory in the arena of the OverlayFs. Children are kept as linked
+// lists, lookups by name go through the index of the OverlayFs.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ODirectory {
-    name: String,
-    size: u64,
-    files: HashMap<String, FileInfo>,
-    directories: HashMap<String, ODirectory>,
+    pub(super) parent: NodeId,
+    pub(super) name: NameId,
+    pub(super) size: u64,
+    pub(super) first_dir: NodeId,
+    pub(super) first_file: NodeId,
+    // Next directory with the same parent
+    pub(super) next: NodeId,
+    // Only known for directories which have an entry in a layer
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) metadata: Option<Box<Metadata>>,
 }
 
 impl ODirectory {
-    pub fn new(name: &str) -> ODirectory {
+    pub(super) fn new(parent: NodeId, name: NameId) -> ODirectory {
         ODirectory {
-            name: String::from(name),
+            parent,
+            name,
             size: 0,
-            files: HashMap::new(),
-            directories: HashMap::new(),
+            first_dir: NO_NODE,
+            first_file: NO_NODE,
+            next: NO_NODE,
+            metadata: None,
         }
     }
+}
 
-    pub fn size(&self) -> u64 {
-        self.size
-    }
+#[derive(Clone, Copy, PartialEq)]
+pub enum TreeSort {
+    Name,
+    Size,
+}
 
-    pub fn get_dir_at_path(&mut self, path: &str) -> Option<&mut ODirectory> {
-        if path == "/" || path.is_empty() {
-            Some(self)
-        } else if self.directories.contains_key(get_leading_entry(path)) {
-            let odir = self.directories.get_mut(get_leading_entry(path)).unwrap();
-            odir.get_dir_at_path(get_remaining(path))
-        } else {
-            None
+// What `cake tree` prints, children smaller than min_size are summed up in one line
+pub struct TreeOptions {
+    pub depth: Option<usize>,
+    pub sort: TreeSort,
+    pub min_size: u64,
+}
+
+impl Default for TreeOptions {
+    fn default() -> TreeOptions {
+        TreeOptions {
+            depth: None,
+            sort: TreeSort::Name,
+            min_size: 0,
         }
     }
+}
 
-    pub fn add_dir(&mut self, d: ODirectory) {
-        self.directories.insert(d.name.clone(), d);
-    }
+pub enum Node<'a> {
+    Dir(DirEntry<'a>),
+    File(FileEntry<'a>),
+}
 
-    pub fn add_dir_path(&mut self, path: &str) {
-        if path.is_empty() || path == "/" {
-        } else if self.directories.contains_key(get_leading_entry(path)) {
-            let dir = self.directories.get_mut(get_leading_entry(path)).unwrap();
-            dir.add_dir_path(get_remaining(path))
-        } else {
-            let fragment = get_leading_entry(path);
-            let mut d = ODirectory::new(fragment);
-            d.add_dir_path(get_remaining(path));
-            self.add_dir(d);
+impl<'a> Node<'a> {
+    pub fn name(&self) -> &'a str {
+        match self {
+            Node::Dir(d) => d.name(),
+            Node::File(f) => f.name(),
         }
     }
 
-    pub fn add_file(&mut self, path: &str, size: u64, layer_id: &str) {
-        let (p, name) = split_last_entry(path);
-
-        if let Some(d) = self.get_dir_at_path(p) {
-            return match d.files.get_mut(name) {
-                Some(f) => {
-                    f.add_version(size, layer_id);
-                    // d.add_with_existing_file(name, file);
-                }
-                None => {
-                    let file = FileInfo::new(name, size, layer_id, p);
-                    d.files.insert(name.to_string(), file);
-                }
-            };
+    // Includes the versions hidden by later layers
+    pub fn size(&self) -> u64 {
+        match self {
+            Node::Dir(d) => d.size(),
+            Node::File(f) => f.total_size,
         }
-
-        self.add_dir_path(p);
-        self.add_file(path, size, layer_id);
     }
+}
 
-    fn mark_children_delete(&mut self, layer_id: &str) {
-        for (_name, f) in self.files.iter_mut() {
-            f.delete(layer_id)
-        }
+#[derive(Clone, Copy)]
+pub struct DirEntry<'a> {
+    ofs: &'a OverlayFs,
+    id: NodeId,
+}
 
-        for (_n, d) in self.directories.iter_mut() {
-            d.mark_children_delete(layer_id);
-        }
+impl<'a> DirEntry<'a> {
+    pub(super) fn new(ofs: &'a OverlayFs, id: NodeId) -> DirEntry<'a> {
+        DirEntry { ofs, id }
     }
 
-    pub fn mark_for_delete(&mut self, path: &str, layer_id: &str) {
-        let (p, name) = split_last_entry(path);
+    fn dir(&self) -> &'a ODirectory {
+        self.ofs.dir(self.id)
+    }
 
-        if let Some(d) = self.get_dir_at_path(p) {
-            if let Some(dir) = d.directories.get_mut(name) {
-                trace!("Marking children for delete at {}", dir.name);
-                dir.mark_children_delete(layer_id);
-            } else if let Some(f) = d.files.get_mut(name) {
-                f.delete(layer_id);
-            }
-        }
+    pub fn name(&self) -> &'a str {
+        self.ofs.name(self.dir().name)
     }
 
-    pub fn show_dir(&self, levels: usize) {
-        let padding = BRANCH_SPACE.repeat(levels);
-        let marker = MIDDLE_ITEM;
-        for (name, dir) in self.directories.iter() {
-            println!("{}{} {} - {}", padding, marker, name, size_human(dir.size));
-            dir.show_dir(levels + 1);
-        }
-        for file in self.files.values() {
-            println!("{}{} {}", padding, marker, file.show_file());
-        }
+    // Set by OverlayFs::update_sizes
+    pub fn size(&self) -> u64 {
+        self.dir().size
+    }
+
+    pub fn dirs(&self) -> impl Iterator<Item = DirEntry<'a>> {
+        let ofs = self.ofs;
+        let mut next = self.dir().first_dir;
+        std::iter::from_fn(move || {
+            let id = next;
+            (id != NO_NODE).then(|| {
+                next = ofs.dir(id).next;
+                DirEntry::new(ofs, id)
+            })
+        })
     }
 
-    pub fn update_sizes(&mut self) -> u64 {
-        let mut size = 0;
+    pub fn files(&self) -> impl Iterator<Item = FileEntry<'a>> {
+        let ofs = self.ofs;
+        let mut next = self.dir().first_file;
+        std::iter::from_fn(move || {
+            let id = next;
+            (id != NO_NODE).then(|| {
+                next = ofs.file(id).next;
+                FileEntry::new(ofs, id)
+            })
+        })
+    }
 
-        for f in self.files.values() {
-            size += f.total_size
+    // Every file below the directory, at any depth
+    pub fn entries(&self) -> Files<'a> {
+        Files {
+            ofs: self.ofs,
+            dirs: vec![self.id],
+            next: NO_NODE,
         }
+    }
 
-        for d in self.directories.values_mut() {
-            size += d.update_sizes();
+    // Size of the files still in the merged filesystem, None when a whiteout
+    // removed everything below the directory
+    pub fn live_size(&self) -> Option<u64> {
+        let mut files = self.entries().peekable();
+        if files.peek().is_none() {
+            return Some(0);
         }
-
-        self.size = size;
-        size
+        files
+            .filter(|f| !f.is_deleted())
+            .fold(None, |size, f| Some(size.unwrap_or(0) + f.total_size))
     }
 
-    pub fn get_entries(&self) -> Vec<&FileInfo> {
-        let mut files = vec![];
-        files.extend(self.files.values());
-
-        for d in self.directories.values() {
-            files.extend(&d.get_entries())
-        }
+    // Directories and files together, in no particular order
+    pub fn children(&self) -> Vec<Node<'a>> {
+        let mut children: Vec<Node> = self.dirs().map(Node::Dir).collect();
+        children.extend(self.files().map(Node::File));
+        children
+    }
 
-        files
+    // One line per entry below the directory. Children are sorted with the name
+    // breaking ties, so the output only changes when the image does.
+    pub fn tree_lines(&self, options: &TreeOptions) -> Vec<String> {
+        let mut lines = vec![];
+        self.add_tree_lines("", 1, options, &mut lines);
+        lines
     }
 
-    pub fn get_dir(&self, path: &str) -> Option<&ODirectory> {
-        if path == "/" || path.is_empty() {
-            Some(self)
-        } else if self.directories.contains_key(get_leading_entry(path)) {
-            let odir = self.directories.get(get_leading_entry(path)).unwrap();
-            odir.get_dir(get_remaining(path))
-        } else {
-            None
+    fn add_tree_lines(
+        &self,
+        prefix: &str,
+        depth: usize,
+        options: &TreeOptions,
+        lines: &mut Vec<String>,
+    ) {
+        // Deleted entries are only kept for their history, the tree shows the merged filesystem
+        let mut children: Vec<(Node, u64)> = self
+            .children()
+            .into_iter()
+            .filter_map(|c| match &c {
+                Node::Dir(dir) => dir.live_size().map(|size| (c, size)),
+                Node::File(file) if file.is_deleted() => None,
+                Node::File(file) => {
+                    let size = file.total_size;
+                    Some((c, size))
+                }
+            })
+            .collect();
+        children.sort_by(|(a, _), (b, _)| a.name().cmp(b.name()));
+        if options.sort == TreeSort::Size {
+            children.sort_by_key(|(_, size)| std::cmp::Reverse(*size));
+        }
+        let (shown, small): (Vec<_>, Vec<_>) = children
+            .into_iter()
+            .partition(|(_, size)| *size >= options.min_size);
+
+        let count = shown.len() + usize::from(!small.is_empty());
+        for (i, (child, size)) in shown.iter().enumerate() {
+            let (marker, next) = if i + 1 == count {
+                (LAST_ITEM, NO_BRANCH_SPACE)
+            } else {
+                (MIDDLE_ITEM, BRANCH_SPACE)
+            };
+            match child {
+                Node::Dir(dir) => {
+                    lines.push(format!(
+                        "{}{} {} - {}",
+                        prefix,
+                        marker,
+                        dir.name(),
+                        size_human(*size)
+                    ));
+                    if options.depth.is_none_or(|max| depth < max) {
+                        let prefix = format!("{}{}", prefix, next);
+                        dir.add_tree_lines(&prefix, depth + 1, options, lines);
+                    }
+                }
+                Node::File(file) => {
+                    lines.push(format!("{}{} {}", prefix, marker, file.show_file()))
+                }
+            }
+        }
+        if !small.is_empty() {
+            let size: u64 = small.iter().map(|(_, size)| size).sum();
+            lines.push(format!(
+                "{}{} … {} more ({})",
+                prefix,
+                LAST_ITEM,
+                small.len(),
+                size_human(size)
+            ));
         }
     }
+}
 
-    pub fn get_file(&self, path: &str) -> Option<&FileInfo> {
-        let (p, name) = split_last_entry(path);
+// Walks a subtree without collecting it first
+pub struct Files<'a> {
+    ofs: &'a OverlayFs,
+    dirs: Vec<NodeId>,
+    next: NodeId,
+}
 
-        if let Some(d) = self.get_dir(p) {
-            d.files.get(name)
-        } else {
-            None
+impl<'a> Iterator for Files<'a> {
+    type Item = FileEntry<'a>;
+
+    fn next(&mut self) -> Option<FileEntry<'a>> {
+        while self.next == NO_NODE {
+            let dir = self.ofs.dir(self.dirs.pop()?);
+            self.next = dir.first_file;
+            let mut child = dir.first_dir;
+            while child != NO_NODE {
+                self.dirs.push(child);
+                child = self.ofs.dir(child).next;
+            }
         }
+        let id = self.next;
+        self.next = self.ofs.file(id).next;
+        Some(FileEntry::new(self.ofs, id))
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::ofs::directory::ODirectory;
-    use crate::ofs::file_info::FileInfo;
-    use std::collections::HashMap;
+    use crate::ofs::directory::{TreeOptions, TreeSort};
+    use crate::ofs::ofs::OverlayFs;
 
     #[test]
     fn gets_dir_at_path() {
-        let mut root = ODirectory::new("/");
-
-        let path = "/usr/local/bin/";
-        root.add_dir_path(path);
+        let mut ofs = OverlayFs::new();
+        ofs.add_dir("/usr/local/bin/", "abc", None).unwrap();
 
-        let dir = root.get_dir_at_path("/usr/local/bin").unwrap();
-        assert_eq!(dir.name, "bin")
+        let dir = ofs.get_dir("/usr/local/bin").unwrap();
+        assert_eq!(dir.name(), "bin");
+        assert!(ofs.get_dir("usr/local/").is_some());
     }
 
     #[test]
     fn gets_file_at_path() {
-        let mut root = ODirectory::new("/");
-        let path = "/usr/local/bin/git";
-        root.add_file(path, 200, "abc");
+        let mut ofs = OverlayFs::new();
+        ofs.add_file("/usr/local/bin/git", 200, "abc");
 
-        let file = root.get_file("/usr/local/bin/git").unwrap();
-        assert_eq!(file.name, "git");
+        let file = ofs.get_file("/usr/local/bin/git").unwrap();
+        assert_eq!(file.name(), "git");
+        assert_eq!(file.path(), "usr/local/bin/git");
         assert_eq!(file.size, 200);
     }
 
     #[test]
     fn add_file_at_path() {
-        let mut root = ODirectory::new("/");
-        root.add_dir_path("/usr/");
-        root.add_file("/usr/local/bin/git", 30000, "test");
+        let mut ofs = OverlayFs::new();
+        ofs.add_dir("/usr/", "test", None).unwrap();
+        ofs.add_file("/usr/local/bin/git", 30000, "test");
 
-        let dir = root.get_dir_at_path("/usr/local/bin/").unwrap();
-        let file = dir.files.get("git").unwrap();
-        assert_eq!(file.name, "git");
+        let dir = ofs.get_dir("/usr/local/bin/").unwrap();
+        let file = dir.files().find(|f| f.name() == "git").unwrap();
         assert_eq!(file.size, 30000);
         assert_eq!(file.total_size, 30000);
+        assert_eq!(file.layer_id(), "test");
     }
 
     #[test]
     fn adds_new_version_to_a_path() {
-        let mut root = ODirectory::new("/");
-        root.add_dir_path("/usr/");
-        root.add_file("/usr/local/bin/git", 30000, "test");
+        let mut ofs = OverlayFs::new();
+        ofs.add_dir("/usr/", "test", None).unwrap();
+        ofs.add_file("/usr/local/bin/git", 30000, "test");
 
-        root.add_file("/usr/local/bin/git", 40000, "test2");
+        ofs.add_file("/usr/local/bin/git", 40000, "test2");
 
-        let bin = root.get_dir_at_path("/usr/local/bin").unwrap();
-        let f = bin.files.get("git").unwrap();
+        let bin = ofs.get_dir("/usr/local/bin").unwrap();
+        let f = bin.files().find(|f| f.name() == "git").unwrap();
 
-        assert_eq!(f.name, "git");
         assert_eq!(f.total_size, 70000);
+        assert_eq!(f.layer_name(f.versions[0].layer), "test2");
     }
 
     #[test]
     fn updates_size_of_dirs() {
-        let mut root = ODirectory::new("/");
-        root.add_dir_path("/usr/");
-        root.add_file("/usr/local/bin/git", 30000, "test");
-        root.add_file("/usr/local/bin/git", 40000, "test2");
-        root.add_file("/usr/local/brew", 1200, "test3");
-
-        root.update_sizes();
-        assert_eq!(root.size, 71200);
-        assert_eq!(root.get_dir_at_path("/usr").unwrap().size, 71200);
-        assert_eq!(root.get_dir_at_path("/usr/local").unwrap().size, 71200);
-        assert_eq!(root.get_dir_at_path("/usr/local/bin").unwrap().size, 70000);
+        let mut ofs = OverlayFs::new();
+        ofs.add_dir("/usr/", "test", None).unwrap();
+        ofs.add_file("/usr/local/bin/git", 30000, "test");
+        ofs.add_file("/usr/local/bin/git", 40000, "test2");
+        ofs.add_file("/usr/local/brew", 1200, "test3");
+
+        ofs.update_sizes();
+        assert_eq!(ofs.size(), 71200);
+        assert_eq!(ofs.get_dir("/usr").unwrap().size(), 71200);
+        assert_eq!(ofs.get_dir("/usr/local").unwrap().size(), 71200);
+        assert_eq!(ofs.get_dir("/usr/local/bin").unwrap().size(), 70000);
+        assert_eq!(ofs.get_dir("/usr").unwrap().entries().count(), 2);
+    }
+
+    #[test]
+    fn prints_tree_sorted_and_cut() {
+        let mut ofs = OverlayFs::new();
+        ofs.add_file("/usr/bin/git", 3000, "layer0");
+        ofs.add_file("/usr/bin/ls", 100, "layer0");
+        ofs.add_file("/usr/bin/cat", 50, "layer0");
+        ofs.add_file("/usr/lib/libz.so", 2000, "layer0");
+        ofs.add_file("/usr/lib/deep/a", 10, "layer0");
+        ofs.add_file("/etc/motd", 20, "layer0");
+        ofs.update_sizes();
+        let root = ofs.get_dir("/").unwrap();
+
+        let lines = root.tree_lines(&TreeOptions::default());
+        assert_eq!(
+            lines,
+            vec![
+                "├─ etc - 20.0 B",
+                "│   └─ motd - (20.0 B)",
+                "└─ usr - 5.2 KB",
+                "    ├─ bin - 3.1 KB",
+                "    │   ├─ cat - (50.0 B)",
+                "    │   ├─ git - (3.0 KB)",
+                "    │   └─ ls - (100.0 B)",
+                "    └─ lib - 2.0 KB",
+                "        ├─ deep - 10.0 B",
+                "        │   └─ a - (10.0 B)",
+                "        └─ libz.so - (2.0 KB)",
+            ]
+        );
+
+        let options = TreeOptions {
+            depth: Some(2),
+            sort: TreeSort::Size,
+            min_size: 1000,
+        };
+        let usr = ofs.get_dir("/usr").unwrap();
+        assert_eq!(
+            usr.tree_lines(&options),
+            vec![
+                "├─ bin - 3.1 KB",
+                "│   ├─ git - (3.0 KB)",
+                "│   └─ … 2 more (150.0 B)",
+                "└─ lib - 2.0 KB",
+                "    ├─ libz.so - (2.0 KB)",
+                "    └─ … 1 more (10.0 B)",
+            ]
+        );
+    }
+
+    #[test]
+    fn leaves_deleted_entries_out_of_the_tree() {
+        let mut ofs = OverlayFs::new();
+        ofs.add_file("/usr/bin/git", 3000, "layer0");
+        ofs.add_file("/usr/bin/ls", 100, "layer0");
+        ofs.add_file("/tmp/build/app.o", 5000, "layer0");
+        ofs.add_file("/tmp/build.log", 20, "layer0");
+        ofs.add_file("/usr/bin/.wh.git", 0, "layer1");
+        ofs.add_file("/tmp/.wh.build", 0, "layer1");
+        ofs.update_sizes();
+        let root = ofs.get_dir("/").unwrap();
+
+        assert_eq!(
+            root.tree_lines(&TreeOptions::default()),
+            vec![
+                "├─ tmp - 20.0 B",
+                "│   └─ build.log - (20.0 B)",
+                "└─ usr - 100.0 B",
+                "    └─ bin - 100.0 B",
+                "        └─ ls - (100.0 B)",
+            ]
+        );
+        assert_eq!(root.live_size(), Some(120));
+        assert_eq!(ofs.get_dir("/tmp/build").unwrap().live_size(), None);
+
+        let options = TreeOptions {
+            min_size: 50,
+            ..TreeOptions::default()
+        };
+        assert_eq!(
+            root.tree_lines(&options),
+            vec![
+                "├─ usr - 100.0 B",
+                "│   └─ bin - 100.0 B",
+                "│       └─ ls - (100.0 B)",
+                "└─ … 1 more (20.0 B)",
+            ]
+        );
     }
 }
@@ -1,133 +1,257 @@
+use crate::ofs::names::NameId;
+use crate::ofs::ofs::{LayerIdx, NodeId, OverlayFs, NO_NODE};
 use crate::ofs::utils::size_human;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::ops::Deref;
+
+// What the tar header says about an entry besides its size
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Metadata {
+    pub mode: u32,
+    pub uid: u64,
+    pub gid: u64,
+    pub mtime: u64,
+    // Extended attributes like security.capability, the values are binary
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub xattrs: BTreeMap<String, Vec<u8>>,
+    // sha256 of the content, only computed when asked for
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub digest: Option<String>,
+}
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct VersionFile {
     pub deleted: bool,
     pub size: u64,
-    pub layer_id: String,
+    pub layer: LayerIdx,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<Box<Metadata>>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkKind {
+    Symlink,
+    Hardlink,
+}
+
+// Symlink targets are kept as written, hardlink targets are relative to the root
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Link {
+    pub kind: LinkKind,
+    pub target: String,
+}
+
+impl Link {
+    pub fn symlink(target: &str) -> Link {
+        Link {
+            kind: LinkKind::Symlink,
+            target: target.to_string(),
+        }
+    }
+
+    pub fn hardlink(target: &str) -> Link {
+        Link {
+            kind: LinkKind::Hardlink,
+            target: target.to_string(),
+        }
+    }
 }
 
+// A file in the arena of the OverlayFs, its name and path are looked up
+// through FileEntry
 #[derive(Debug, Serialize, Deserialize)]
 pub struct FileInfo {
-    pub name: String,
+    pub(super) dir: NodeId,
+    pub(super) name: NameId,
+    // Next file in the same directory
+    pub(super) next: NodeId,
     pub size: u64,
-    pub layer_id: String,
-    pub path: String,
+    pub layer: LayerIdx,
     pub total_size: u64,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub versions: Vec<VersionFile>,
+    // What the latest version links to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub link: Option<Box<Link>>,
+    // Metadata of the first version, the others keep their own
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<Box<Metadata>>,
 }
 
 impl FileInfo {
-    pub fn new(name: &str, size: u64, layer_id: &str, path: &str) -> FileInfo {
+    pub fn new(dir: NodeId, name: NameId, size: u64, layer: LayerIdx) -> FileInfo {
         FileInfo {
-            name: name.to_string(),
+            dir,
+            name,
+            next: NO_NODE,
             size,
-            layer_id: layer_id.to_string(),
-            path: path.to_string(),
+            layer,
             total_size: size,
             versions: vec![],
+            link: None,
+            metadata: None,
+        }
+    }
+
+    pub fn set_metadata(&mut self, metadata: Option<Metadata>) {
+        let metadata = metadata.map(Box::new);
+        match self.versions.last_mut() {
+            Some(v) => v.metadata = metadata,
+            None => self.metadata = metadata,
         }
     }
 
-    pub fn delete(&mut self, layer_id: &str) {
+    // A deleted file is only kept for its history
+    pub fn is_deleted(&self) -> bool {
+        self.versions.last().is_some_and(|v| v.deleted)
+    }
+
+    pub fn delete(&mut self, layer: LayerIdx) {
         let delete_file = VersionFile {
             deleted: true,
             size: 0,
-            layer_id: layer_id.to_string(),
+            layer,
+            metadata: None,
         };
 
         self.versions.push(delete_file);
     }
 
-    pub fn add_version(&mut self, size: u64, layer_id: &str) {
+    pub fn add_version(&mut self, size: u64, layer: LayerIdx) {
         let version = VersionFile {
             deleted: false,
             size,
-            layer_id: layer_id.to_string(),
+            layer,
+            metadata: None,
         };
 
         self.total_size += size;
         self.versions.push(version);
     }
+}
+
+// A file of the OverlayFs together with what names it
+#[derive(Clone, Copy)]
+pub struct FileEntry<'a> {
+    ofs: &'a OverlayFs,
+    info: &'a FileInfo,
+}
+
+impl<'a> FileEntry<'a> {
+    pub(super) fn new(ofs: &'a OverlayFs, id: NodeId) -> FileEntry<'a> {
+        FileEntry {
+            ofs,
+            info: ofs.file(id),
+        }
+    }
+
+    pub fn info(&self) -> &'a FileInfo {
+        self.info
+    }
+
+    pub fn name(&self) -> &'a str {
+        self.ofs.name(self.info.name)
+    }
+
+    // The path from the root without the leading slash, built when asked for
+    pub fn path(&self) -> String {
+        let mut path = self.ofs.dir_path(self.info.dir);
+        path.push_str(self.name());
+        path
+    }
+
+    // The layer which added the first version
+    pub fn layer_id(&self) -> &'a str {
+        self.ofs.layer_id(self.info.layer)
+    }
+
+    pub fn layer_name(&self, layer: LayerIdx) -> &'a str {
+        self.ofs.layer_id(layer)
+    }
 
     pub fn show_file(&self) -> String {
-        format!("{} - ({})", self.name, size_human(self.total_size))
+        match self.info.link.as_deref() {
+            Some(Link {
+                kind: LinkKind::Symlink,
+                target,
+            }) => format!("{} -> {}", self.name(), target),
+            Some(Link {
+                kind: LinkKind::Hardlink,
+                target,
+            }) => format!("{} -> /{} (hard link)", self.name(), target),
+            None if self.is_deleted() => {
+                format!(
+                    "{} - ({}, deleted)",
+                    self.name(),
+                    size_human(self.total_size)
+                )
+            }
+            None => format!("{} - ({})", self.name(), size_human(self.total_size)),
+        }
+    }
+}
+
+impl<'a> Deref for FileEntry<'a> {
+    type Target = FileInfo;
+
+    fn deref(&self) -> &FileInfo {
+        self.info
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::{FileInfo, VersionFile};
-    use crate::ofs::ofs::OverlayFs;
+    use crate::ofs::ofs::NO_NODE;
 
     #[test]
     fn adds_version_for_a_file() {
-        let mut file = FileInfo {
-            name: "name".to_string(),
-            size: 10,
-            layer_id: "".to_string(),
-            path: "".to_string(),
-            total_size: 10,
-            versions: vec![],
-        };
+        let mut file = FileInfo::new(0, 0, 10, 0);
 
-        file.add_version(20, "lay2");
+        file.add_version(20, 1);
         assert_eq!(file.versions.len(), 1);
         assert_eq!(file.total_size, 30);
     }
 
     #[test]
     fn marks_file_for_delete() {
-        let mut file = FileInfo {
-            name: "name".to_string(),
-            size: 10,
-            layer_id: "".to_string(),
-            path: "".to_string(),
-            total_size: 10,
-            versions: vec![],
-        };
+        let mut file = FileInfo::new(0, 0, 10, 0);
 
-        file.delete("lay2");
+        file.delete(1);
         assert_eq!(file.versions.len(), 1);
         assert_eq!(file.total_size, 10);
+        assert!(file.is_deleted());
     }
 
     #[test]
     fn gets_the_size_of_files() {
-        let mut multi = FileInfo {
-            name: "/".to_string(),
-            size: 200,
-            total_size: 200,
-            path: "/".to_string(),
-            versions: vec![],
-            layer_id: "".to_string(),
-        };
-        multi.add_version(200, "lay2");
-        multi.add_version(50, "lay3");
+        let mut multi = FileInfo::new(0, 0, 200, 0);
+        multi.add_version(200, 1);
+        multi.add_version(50, 2);
 
         assert_eq!(multi.total_size, 450);
     }
 
     #[test]
     fn serialises_multi_file_info() {
-        let multi = FileInfo {
-            name: "file1".to_string(),
-            size: 50,
-            total_size: 450,
-            path: "/".to_string(),
-            layer_id: "lay1".to_string(),
-            versions: vec![VersionFile {
-                deleted: false,
-                size: 400,
-                layer_id: "lay2".to_string(),
-            }],
-        };
+        let mut multi = FileInfo::new(0, 3, 50, 0);
+        multi.total_size = 450;
+        multi.versions.push(VersionFile {
+            deleted: false,
+            size: 400,
+            layer: 1,
+            metadata: None,
+        });
 
         let result = serde_json::to_string(&multi).unwrap();
         assert_eq!(
             result,
-            r#"{"name":"file1","size":50,"layer_id":"lay1","path":"/","total_size":450,"versions":[{"deleted":false,"size":400,"layer_id":"lay2"}]}"#
+            format!(
+                r#"{{"dir":0,"name":3,"next":{},"size":50,"layer":0,"total_size":450,"versions":[{{"deleted":false,"size":400,"layer":1}}]}}"#,
+                NO_NODE
+            )
         );
     }
 }
@@ -1,13 +1,145 @@
+use crate::image::config::History;
 use serde::{Deserialize, Serialize};
 
+// Longer build instructions are cut when shown
+const MAX_INSTRUCTION: usize = 80;
+
+// Byte range of a layer tar stored inside an image archive
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ArchiveEntry {
+    pub offset: u64,
+    pub length: u64,
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct Layer {
     pub layer_id: String,
     pub path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub entry: Option<ArchiveEntry>,
+    // Step in the build history, or the position of the layer when there is no history
+    #[serde(default)]
+    pub index: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_by: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created: Option<String>,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub empty_layer: bool,
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+// The writable layer of a container, on top of the image layers
+pub const CONTAINER_LAYER: &str = "container";
+
+pub fn layer_id(index: usize) -> String {
+    format!("layer{}", index)
 }
 
 impl Layer {
-    pub fn new(layer_id: String, path: String) -> Layer {
-        Layer { layer_id, path }
+    pub fn new(index: usize, path: String) -> Layer {
+        Layer {
+            layer_id: layer_id(index),
+            path,
+            entry: None,
+            index,
+            created_by: None,
+            created: None,
+            empty_layer: false,
+        }
+    }
+
+    pub fn in_archive(index: usize, path: String, entry: ArchiveEntry) -> Layer {
+        Layer {
+            entry: Some(entry),
+            ..Layer::new(index, path)
+        }
+    }
+
+    pub fn container(index: usize, path: String, id: &str) -> Layer {
+        Layer {
+            layer_id: CONTAINER_LAYER.to_string(),
+            created_by: Some(format!("container {}", id)),
+            ..Layer::new(index, path)
+        }
+    }
+
+    // Build steps like ENV or CMD only change the config, they have no files
+    pub fn empty(index: usize, history: &History) -> Layer {
+        let mut layer = Layer {
+            layer_id: format!("empty{}", index),
+            ..Layer::new(index, String::new())
+        };
+        layer.set_history(index, history);
+        layer
+    }
+
+    pub fn set_history(&mut self, index: usize, history: &History) {
+        self.index = index;
+        self.created_by = history.created_by.clone();
+        self.created = history.created.clone();
+        self.empty_layer = history.empty_layer;
+    }
+
+    // created_by as written in the Dockerfile, docker and buildkit record the shell around it
+    pub fn instruction(&self) -> String {
+        let created_by = match &self.created_by {
+            Some(created_by) => created_by.trim(),
+            None => return String::new(),
+        };
+        let created_by = created_by.trim_end_matches("# buildkit").trim();
+
+        let instruction = if let Some(nop) = created_by.strip_prefix("/bin/sh -c #(nop)") {
+            nop.trim().to_string()
+        } else if let Some(run) = created_by.strip_prefix("/bin/sh -c ") {
+            format!("RUN {}", run.trim())
+        } else if let Some(run) = created_by.strip_prefix("RUN /bin/sh -c ") {
+            format!("RUN {}", run.trim())
+        } else {
+            created_by.to_string()
+        };
+
+        if instruction.chars().count() > MAX_INSTRUCTION {
+            let cut: String = instruction.chars().take(MAX_INSTRUCTION - 3).collect();
+            return format!("{}...", cut);
+        }
+        instruction
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::image::config::History;
+    use crate::ofs::layer::Layer;
+
+    fn created_by(created_by: &str) -> String {
+        let history = History {
+            created_by: Some(created_by.to_string()),
+            ..Default::default()
+        };
+        let mut layer = Layer::new(0, String::new());
+        layer.set_history(3, &history);
+        layer.instruction()
+    }
+
+    #[test]
+    fn shows_build_instruction() {
+        assert_eq!(
+            created_by("/bin/sh -c #(nop)  CMD [\"sh\"]"),
+            "CMD [\"sh\"]"
+        );
+        assert_eq!(
+            created_by("/bin/sh -c apt-get install -y git"),
+            "RUN apt-get install -y git"
+        );
+        assert_eq!(
+            created_by("RUN /bin/sh -c make install # buildkit"),
+            "RUN make install"
+        );
+        assert_eq!(created_by("COPY app /app # buildkit"), "COPY app /app");
+        assert_eq!(created_by(&"x".repeat(100)).len(), 80);
     }
 }
@@ -2,5 +2,7 @@
 pub mod directory;
 pub mod file_info;
 pub mod layer;
+pub mod names;
+#[allow(clippy::module_inception)]
 pub mod ofs;
 pub mod utils;
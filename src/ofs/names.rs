@@ -0,0 +1,77 @@
+// File names are stored once, node_modules and site-packages repeat the same
+// few names (index.js, __init__.py, LICENSE) hundreds of thousands of times.
+use serde::de::{Deserialize, Deserializer};
+use serde::ser::{Serialize, SerializeSeq, Serializer};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+pub type NameId = u32;
+
+#[derive(Default)]
+pub struct Names {
+    names: Vec<Arc<str>>,
+    ids: HashMap<Arc<str>, NameId>,
+}
+
+impl Names {
+    pub fn intern(&mut self, name: &str) -> NameId {
+        if let Some(id) = self.ids.get(name) {
+            return *id;
+        }
+        let id = self.names.len() as NameId;
+        let name: Arc<str> = Arc::from(name);
+        self.names.push(name.clone());
+        self.ids.insert(name, id);
+        id
+    }
+
+    // A name never interned is on no path
+    pub fn get(&self, name: &str) -> Option<NameId> {
+        self.ids.get(name).copied()
+    }
+
+    pub fn name(&self, id: NameId) -> &str {
+        &self.names[id as usize]
+    }
+}
+
+// Stored as the list of names, ids are their positions
+impl Serialize for Names {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(self.names.len()))?;
+        for name in self.names.iter() {
+            seq.serialize_element(name.as_ref())?;
+        }
+        seq.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for Names {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Names, D::Error> {
+        let mut names = Names::default();
+        for name in Vec::<String>::deserialize(deserializer)? {
+            names.intern(&name);
+        }
+        Ok(names)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ofs::names::Names;
+
+    #[test]
+    fn interns_names_once() {
+        let mut names = Names::default();
+        let index = names.intern("index.js");
+        assert_eq!(names.intern("package.json"), 1);
+        assert_eq!(names.intern("index.js"), index);
+        assert_eq!(names.name(index), "index.js");
+        assert_eq!(names.get("LICENSE"), None);
+
+        let json = serde_json::to_string(&names).unwrap();
+        assert_eq!(json, r#"["index.js","package.json"]"#);
+        let names: Names = serde_json::from_str(&json).unwrap();
+        assert_eq!(names.get("package.json"), Some(1));
+    }
+}
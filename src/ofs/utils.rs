@@ -17,17 +17,21 @@ pub fn size_human(size: u64) -> String {
     format!("{:.1} {}", sizef, SIZE[index])
 }
 
-pub fn get_leading_entry(path: &str) -> &str {
-    let mut retval = path;
-    if let Some(0) = retval.find('/') {
-        retval = &retval[1..];
-    }
-
-    if let Some(index) = retval.find('/') {
-        &retval[0..index]
-    } else {
-        retval
-    }
+// Reads sizes the way size_human prints them: "1.5 MB", "500KB", "20k" or plain bytes
+pub fn parse_size(size: &str) -> Option<u64> {
+    let size = size.trim();
+    let split = size
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(size.len());
+    let (number, unit) = size.split_at(split);
+    let number: f64 = number.parse().ok()?;
+    let unit = match unit.trim().to_uppercase() {
+        u if u.is_empty() => "B".to_string(),
+        u if u.len() == 1 && u != "B" => format!("{}B", u),
+        u => u,
+    };
+    let power = SIZE.iter().position(|u| *u == unit)?;
+    Some((number * 1000_f64.powi(power as i32)) as u64)
 }
 
 pub fn split_last_entry(path: &str) -> (&str, &str) {
@@ -51,51 +55,40 @@ pub fn split_last_entry(path: &str) -> (&str, &str) {
     }
 }
 
-pub fn get_remaining(path: &str) -> &str {
-    let mut retval = path;
-    if let Some(0) = retval.find('/') {
-        retval = &retval[1..];
-    }
-
-    if let Some(index) = retval.find('/') {
-        &retval[index..]
-    } else {
-        ""
-    }
-}
-
 #[cfg(test)]
 mod tests {
-    use super::{get_leading_entry, get_remaining, split_last_entry};
+    use super::{parse_size, split_last_entry};
 
     #[test]
-    fn gets_leading_entry() {
+    fn splits_last_entry() {
         let path1 = "usr/local/bin/";
-        assert_eq!(get_leading_entry(path1), "usr");
-        assert_eq!(get_remaining(path1), "/local/bin/");
         let (p1, f1) = split_last_entry(path1);
         assert_eq!(p1, "usr/local");
         assert_eq!(f1, "bin");
 
         let path2 = "/dev/null";
-        assert_eq!(get_leading_entry(path2), "dev");
-        assert_eq!(get_remaining(path2), "/null");
         let (p2, f2) = split_last_entry(path2);
         assert_eq!(p2, "/dev/");
         assert_eq!(f2, "null");
 
         let path3 = "usrlocal";
-        assert_eq!(get_leading_entry(path3), "usrlocal");
-        assert_eq!(get_remaining(path3), "");
         let (p3, f3) = split_last_entry(path3);
         assert_eq!(p3, "");
         assert_eq!(f3, "usrlocal");
 
         let path4 = "test/";
-        assert_eq!(get_leading_entry(path4), "test");
-        assert_eq!(get_remaining(path4), "/");
         let (p4, f4) = split_last_entry(path4);
         assert_eq!(p4, "");
         assert_eq!(f4, "test");
     }
+
+    #[test]
+    fn parses_sizes() {
+        assert_eq!(parse_size("1500"), Some(1500));
+        assert_eq!(parse_size("1.5 MB"), Some(1_500_000));
+        assert_eq!(parse_size("20k"), Some(20_000));
+        assert_eq!(parse_size("2GB"), Some(2_000_000_000));
+        assert_eq!(parse_size("10 parsecs"), None);
+        assert_eq!(parse_size("MB"), None);
+    }
 }
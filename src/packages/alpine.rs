@@ -17,6 +17,7 @@ impl<'a> AlpinePackageManager<'a> {
         AlpinePackageManager { ofs }
     }
 
+    #[allow(dead_code)]
     pub fn get_packages_from_docker() {
         // docker run --rm -it alpine-pkg /sbin/apk -v info
     }
@@ -66,7 +67,7 @@ impl<'a> PackageManager for AlpinePackageManager<'a> {
     }
 
     fn get_installed_packages(self) -> Vec<Package> {
-        if let Some(content) = get_file_from_image(&self.ofs, INSTALLED_FILE) {
+        if let Some(content) = get_file_from_image(self.ofs, INSTALLED_FILE) {
             trace!("Received status file");
             let mut pkgs = parse_installed_status_file(&content);
             pkgs.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
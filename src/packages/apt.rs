@@ -23,6 +23,7 @@ impl<'a> DebianPackageManager<'a> {
         }
     }
 
+    #[allow(dead_code)]
     pub fn get_packages_from_docker() {
         // docker run --rm -it alpine-pkg /usr/bin/apt list --installed
     }
@@ -166,12 +167,12 @@ Depends: libc6 (>= 2.14)"#;
         assert_eq!(installed.len(), 2);
         assert_eq!(installed[0].name, "less");
         assert_eq!(installed[0].version, "551-2");
-        assert_eq!(installed[0].optional, false);
+        assert!(!installed[0].optional);
         assert_eq!(installed[0].size, 296000);
 
         assert_eq!(installed[1].name, "libacl1");
         assert_eq!(installed[1].version, "2.2.53-10");
-        assert_eq!(installed[1].optional, true);
+        assert!(installed[1].optional);
         assert_eq!(installed[1].size, 71000);
     }
 }
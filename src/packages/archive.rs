@@ -1,5 +1,5 @@
 use super::super::ofs::ofs::OverlayFs;
-use crate::ofs::file_info::FileInfo;
+use crate::ofs::file_info::FileEntry;
 use crate::packages::deps::{Cache, Package, PackageManager};
 use crate::packages::util::get_single_version_in_dir;
 use log::trace;
@@ -9,11 +9,11 @@ const EXTENSIONS: [&str; 5] = [".zip", ".tar", ".tar.gz", ".tar.bz", ".tar.xz"];
 
 const TEMP_AS_CACHE: &str = "/tmp";
 
-pub fn list_archives(ofs: &OverlayFs) -> Vec<&FileInfo> {
+pub fn list_archives(ofs: &OverlayFs) -> Vec<FileEntry<'_>> {
     let mut archives = vec![];
 
     for file in ofs.entries() {
-        if EXTENSIONS.iter().any(|&ext| file.name.ends_with(ext)) {
+        if EXTENSIONS.iter().any(|&ext| file.name().ends_with(ext)) {
             archives.push(file);
         }
     }
@@ -32,6 +32,7 @@ impl<'a> ArchiveManager<'a> {
             packages: get_packages(ofs),
         }
     }
+    #[allow(dead_code)]
     pub fn get_packages_from_docker() {
         // find / -type f '(' -name "*.zip" -o -name "*.tar*" ')' -printf '%P %k\n'
         // docker run --rm -it alpine-pkg /bin/find .
@@ -43,7 +44,7 @@ fn get_packages(ofs: &OverlayFs) -> Vec<Package> {
     for f in list_archives(ofs) {
         let ver = f.versions.len() + 1;
         let pkg = Package {
-            name: format!("{}{}", f.path, f.name),
+            name: f.path(),
             version: ver.to_string(),
             optional: false,
             size: f.total_size,
@@ -92,7 +93,6 @@ impl<'a> PackageManager for ArchiveManager<'a> {
 mod tests {
     use crate::ofs::ofs::OverlayFs;
     use crate::packages::archive::list_archives;
-    use log::LevelFilter::Off;
 
     #[test]
     fn returns_a_list_of_archives() {
@@ -110,15 +110,14 @@ mod tests {
         ];
         let mut ofs = OverlayFs::new();
         for n in file_names.iter() {
-            ofs.add_file(*n, 10, "abc");
+            ofs.add_file(n, 10, "abc");
         }
 
         let archives = list_archives(&ofs);
-        println!("{:?}", archives);
         assert_eq!(archives.len(), 4);
-        assert_eq!(archives.iter().any(|&f| f.name == "arch.zip"), true);
-        assert_eq!(archives.iter().any(|&f| f.name == "java.tar.gz"), true);
-        assert_eq!(archives.iter().any(|&f| f.name == "python.tar.bz"), true);
-        assert_eq!(archives.iter().any(|&f| f.name == "image.tar"), true);
+        assert!(archives.iter().any(|f| f.name() == "arch.zip"));
+        assert!(archives.iter().any(|f| f.name() == "java.tar.gz"));
+        assert!(archives.iter().any(|f| f.name() == "python.tar.bz"));
+        assert!(archives.iter().any(|f| f.name() == "image.tar"));
     }
 }
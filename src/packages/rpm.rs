@@ -1,10 +1,11 @@
-use crate::docker;
+use crate::docker::source::ImageSource;
 use crate::ofs::ofs::OverlayFs;
 use crate::packages::deps::{Cache, Package, PackageManager};
 use crate::packages::util::get_single_version_in_dir;
-use log::trace;
+use log::{trace, warn};
 use std::cmp::Ordering;
 
+#[allow(dead_code)]
 const RPM_MACROS: &str = "/usr/lib/rpm/macros";
 
 //RPM command to extract packages from the rpm database
@@ -24,13 +25,19 @@ const CACHE_DIR: &str = "/var/cache/yum/";
 pub struct RPMPackageManager<'a> {
     ofs: &'a OverlayFs,
     image: &'a str,
+    source: Option<&'a dyn ImageSource>,
 }
 
 impl<'a> RPMPackageManager<'a> {
-    pub fn new(ofs: &'a OverlayFs, image: &'a str) -> RPMPackageManager<'a> {
-        RPMPackageManager { ofs, image }
+    pub fn new(
+        ofs: &'a OverlayFs,
+        image: &'a str,
+        source: Option<&'a dyn ImageSource>,
+    ) -> RPMPackageManager<'a> {
+        RPMPackageManager { ofs, image, source }
     }
 
+    #[allow(dead_code)]
     pub fn get_packages_from_docker() {
         // docker run --rm -it alpine-pkg /usr/bin/apt list --installed
     }
@@ -43,23 +50,30 @@ impl<'a> PackageManager for RPMPackageManager<'a> {
 
     fn get_installed_packages(self) -> Vec<Package> {
         let mut packages = vec![];
-        if self.ofs.get_dir("/usr/lib/rpm/").is_none() {
-            return packages;
-        }
+        let source = match self.source {
+            Some(source) if self.ofs.get_dir("/usr/lib/rpm/").is_some() => source,
+            _ => return packages,
+        };
 
         // Extract the rpm db to folder and let local rpm read it.
-        if let Ok(pkgs) = docker::run(self.image, RPM_CMD.to_vec()) {
-            trace!("Received status file");
-            for pkg in pkgs.lines() {
-                let mut p = pkg.split('\t');
-                let package = Package {
-                    name: p.next().unwrap_or("").to_string(),
-                    version: p.next().unwrap_or("").to_string(),
-                    size: p.next().unwrap_or("0").parse::<u64>().unwrap_or(0),
-                    optional: false,
-                };
-                packages.push(package);
+        let pkgs = match source.run(self.image, RPM_CMD.to_vec()) {
+            Ok(pkgs) => pkgs,
+            Err(e) => {
+                warn!("Failed to list RPM packages of {}: {}", self.image, e);
+                return packages;
             }
+        };
+
+        trace!("Received status file");
+        for pkg in pkgs.lines() {
+            let mut p = pkg.split('\t');
+            let package = Package {
+                name: p.next().unwrap_or("").to_string(),
+                version: p.next().unwrap_or("").to_string(),
+                size: p.next().unwrap_or("0").parse::<u64>().unwrap_or(0),
+                optional: false,
+            };
+            packages.push(package);
         }
         packages.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
         packages
@@ -0,0 +1,187 @@
+use super::reference::DOCKER_HUB;
+use log::trace;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+// Docker hub credentials are stored under the legacy v1 index url
+const DOCKER_HUB_AUTH_KEY: &str = "https://index.docker.io/v1/";
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Credentials {
+    pub username: String,
+    pub password: String,
+}
+
+impl Credentials {
+    pub fn basic(&self) -> String {
+        let token = base64::encode(format!("{}:{}", self.username, self.password));
+        format!("Basic {}", token)
+    }
+}
+
+#[derive(Deserialize)]
+struct AuthEntry {
+    auth: Option<String>,
+    username: Option<String>,
+    password: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct DockerConfig {
+    #[serde(default)]
+    auths: HashMap<String, AuthEntry>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Challenge {
+    pub scheme: String,
+    pub realm: Option<String>,
+    pub service: Option<String>,
+    pub scope: Option<String>,
+}
+
+fn docker_config_path() -> Option<PathBuf> {
+    if let Ok(dir) = env::var("DOCKER_CONFIG") {
+        return Some(PathBuf::from(dir).join("config.json"));
+    }
+    env::var("HOME")
+        .ok()
+        .map(|home| PathBuf::from(home).join(".docker").join("config.json"))
+}
+
+fn host_of(key: &str) -> &str {
+    let key = key
+        .strip_prefix("https://")
+        .or_else(|| key.strip_prefix("http://"))
+        .unwrap_or(key);
+    key.split('/').next().unwrap_or(key)
+}
+
+pub fn credentials_from_config(config: &str, registry: &str) -> Option<Credentials> {
+    let config: DockerConfig = serde_json::from_str(config).ok()?;
+
+    let entry = if registry == DOCKER_HUB {
+        config.auths.get(DOCKER_HUB_AUTH_KEY).or_else(|| {
+            config
+                .auths
+                .iter()
+                .find(|(k, _)| matches!(host_of(k), "docker.io" | "index.docker.io"))
+                .map(|(_, v)| v)
+        })
+    } else {
+        config
+            .auths
+            .iter()
+            .find(|(k, _)| host_of(k) == registry)
+            .map(|(_, v)| v)
+    }?;
+
+    if let Some(auth) = &entry.auth {
+        let decoded = String::from_utf8(base64::decode(auth).ok()?).ok()?;
+        let (username, password) = decoded.split_once(':')?;
+        return Some(Credentials {
+            username: username.to_string(),
+            password: password.to_string(),
+        });
+    }
+
+    match (&entry.username, &entry.password) {
+        (Some(username), Some(password)) => Some(Credentials {
+            username: username.clone(),
+            password: password.clone(),
+        }),
+        _ => None,
+    }
+}
+
+// Reads credentials stored by `docker login`, credential helpers are not supported
+pub fn load_credentials(registry: &str) -> Option<Credentials> {
+    let path = docker_config_path()?;
+    let config = fs::read_to_string(&path).ok()?;
+    trace!("Looking up credentials for {} in {:?}", registry, path);
+    credentials_from_config(&config, registry)
+}
+
+// Parses a WWW-Authenticate header like
+// Bearer realm="https://auth.docker.io/token",service="registry.docker.io",scope="repository:library/alpine:pull"
+pub fn parse_challenge(header: &str) -> Option<Challenge> {
+    let (scheme, params) = match header.trim().split_once(' ') {
+        Some((scheme, params)) => (scheme, params),
+        None => (header.trim(), ""),
+    };
+
+    let mut challenge = Challenge {
+        scheme: scheme.to_string(),
+        realm: None,
+        service: None,
+        scope: None,
+    };
+
+    let mut rest = params.trim();
+    while !rest.is_empty() {
+        let (key, after) = rest.split_once('=')?;
+        let after = after.trim_start();
+        let (value, remaining) = if let Some(quoted) = after.strip_prefix('"') {
+            let end = quoted.find('"')?;
+            (&quoted[..end], &quoted[end + 1..])
+        } else {
+            match after.find(',') {
+                Some(end) => (&after[..end], &after[end..]),
+                None => (after, ""),
+            }
+        };
+
+        match key.trim() {
+            "realm" => challenge.realm = Some(value.to_string()),
+            "service" => challenge.service = Some(value.to_string()),
+            "scope" => challenge.scope = Some(value.to_string()),
+            _ => {}
+        }
+        rest = remaining.trim_start_matches([',', ' ']);
+    }
+    Some(challenge)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::registry::auth::{credentials_from_config, parse_challenge, Credentials};
+    use crate::registry::reference::DOCKER_HUB;
+
+    #[test]
+    fn reads_credentials_from_docker_config() {
+        let config = r#"{"auths":{"https://index.docker.io/v1/":{"auth":"dXNlcjpwYXNz"},"ghcr.io":{"username":"bot","password":"secret"}}}"#;
+
+        assert_eq!(
+            credentials_from_config(config, DOCKER_HUB),
+            Some(Credentials {
+                username: "user".to_string(),
+                password: "pass".to_string()
+            })
+        );
+        assert_eq!(
+            credentials_from_config(config, "ghcr.io").unwrap().password,
+            "secret"
+        );
+        assert_eq!(credentials_from_config(config, "quay.io"), None);
+    }
+
+    #[test]
+    fn parses_bearer_challenge() {
+        let header = r#"Bearer realm="https://auth.docker.io/token",service="registry.docker.io",scope="repository:library/alpine:pull""#;
+        let challenge = parse_challenge(header).unwrap();
+
+        assert_eq!(challenge.scheme, "Bearer");
+        assert_eq!(
+            challenge.realm.as_deref(),
+            Some("https://auth.docker.io/token")
+        );
+        assert_eq!(challenge.service.as_deref(), Some("registry.docker.io"));
+        assert_eq!(
+            challenge.scope.as_deref(),
+            Some("repository:library/alpine:pull")
+        );
+    }
+}
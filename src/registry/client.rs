@@ -0,0 +1,143 @@
+// Minimal client for the Distribution API v2, https://distribution.github.io/distribution/spec/api/
+use super::auth::{parse_challenge, Credentials};
+use super::reference::Reference;
+use crate::error::{Context, Error, Result};
+use log::trace;
+use serde::Deserialize;
+use std::io::Read;
+
+const MANIFEST_TYPES: [&str; 4] = [
+    "application/vnd.oci.image.index.v1+json",
+    "application/vnd.oci.image.manifest.v1+json",
+    "application/vnd.docker.distribution.manifest.list.v2+json",
+    "application/vnd.docker.distribution.manifest.v2+json",
+];
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    token: Option<String>,
+    access_token: Option<String>,
+}
+
+pub struct Client {
+    agent: ureq::Agent,
+    base_url: String,
+    repository: String,
+    credentials: Option<Credentials>,
+    authorization: Option<String>,
+}
+
+impl Client {
+    pub fn new(reference: &Reference, credentials: Option<Credentials>) -> Client {
+        let scheme = if reference.is_local() {
+            "http"
+        } else {
+            "https"
+        };
+
+        Client {
+            agent: ureq::AgentBuilder::new().build(),
+            base_url: format!("{}://{}", scheme, reference.registry),
+            repository: reference.repository.clone(),
+            credentials,
+            authorization: None,
+        }
+    }
+
+    fn request(
+        &self,
+        url: &str,
+        accept: &str,
+    ) -> std::result::Result<ureq::Response, Box<ureq::Error>> {
+        let mut request = self.agent.get(url).set("Accept", accept);
+        if let Some(auth) = &self.authorization {
+            request = request.set("Authorization", auth);
+        }
+        request.call().map_err(Box::new)
+    }
+
+    fn get(&mut self, path: &str, accept: &str) -> Result<ureq::Response> {
+        let url = format!("{}/v2/{}/{}", self.base_url, self.repository, path);
+        trace!("GET {}", url);
+
+        let result = match self.request(&url, accept).map_err(|e| *e) {
+            Err(ureq::Error::Status(401, response)) if self.authorization.is_none() => {
+                let header = response.header("www-authenticate").unwrap_or("");
+                self.authenticate(header)?;
+                self.request(&url, accept).map_err(|e| *e)
+            }
+            result => result,
+        };
+
+        result.map_err(|e| match e {
+            ureq::Error::Status(404, _) => Error::ImageNotFound(url),
+            e => Error::Registry(e.to_string()),
+        })
+    }
+
+    fn authenticate(&mut self, header: &str) -> Result<()> {
+        let challenge = parse_challenge(header).ok_or_else(|| {
+            Error::Registry(format!("Unsupported authentication challenge: {}", header))
+        })?;
+
+        if challenge.scheme.eq_ignore_ascii_case("basic") {
+            let credentials = self.credentials.as_ref().ok_or_else(|| {
+                Error::Registry("Registry requires credentials, run docker login".to_string())
+            })?;
+            self.authorization = Some(credentials.basic());
+            return Ok(());
+        }
+
+        let realm = challenge
+            .realm
+            .ok_or_else(|| Error::Registry(format!("No realm in challenge: {}", header)))?;
+        let scope = challenge
+            .scope
+            .unwrap_or_else(|| format!("repository:{}:pull", self.repository));
+
+        let mut request = self.agent.get(&realm).query("scope", &scope);
+        if let Some(service) = &challenge.service {
+            request = request.query("service", service);
+        }
+        if let Some(credentials) = &self.credentials {
+            request = request.set("Authorization", &credentials.basic());
+        }
+
+        trace!("Requesting token from {}", realm);
+        let response = request
+            .call()
+            .map_err(|e| Error::Registry(format!("Failed to get token from {}: {}", realm, e)))?;
+        let response: TokenResponse =
+            serde_json::from_reader(response.into_reader()).map_err(|e| {
+                Error::Registry(format!("Invalid token response from {}: {}", realm, e))
+            })?;
+
+        let token = response
+            .token
+            .or(response.access_token)
+            .ok_or_else(|| Error::Registry(format!("No token in response from {}", realm)))?;
+        self.authorization = Some(format!("Bearer {}", token));
+        Ok(())
+    }
+
+    // Returns the raw manifest and its media type
+    pub fn get_manifest(&mut self, reference: &str) -> Result<(Vec<u8>, String)> {
+        let response = self.get(
+            &format!("manifests/{}", reference),
+            &MANIFEST_TYPES.join(", "),
+        )?;
+        let media_type = response.content_type().to_string();
+
+        let mut body = vec![];
+        response
+            .into_reader()
+            .read_to_end(&mut body)
+            .context(format!("Failed to read manifest {}", reference))?;
+        Ok((body, media_type))
+    }
+
+    pub fn get_blob(&mut self, digest: &str) -> Result<Box<dyn Read + Send + Sync>> {
+        let response = self.get(&format!("blobs/{}", digest), "*/*")?;
+        Ok(response.into_reader())
+    }
+}
@@ -0,0 +1,368 @@
+// Pulls images straight from a registry, no docker daemon needed.
+// Blobs are stored as an OCI image layout in the image directory.
+mod auth;
+mod client;
+pub mod reference;
+
+use crate::error::{Context, Error, Result};
+use crate::image::config::ImageConfig;
+use crate::image::digest::{sha256_digest, verify_digest};
+use crate::image::image_tar::{apply_layer, parse_blob};
+use crate::image::oci::{
+    blob_path, is_index_media_type, Descriptor, ImageIndex, ImageManifest, Platform, INDEX_FILE,
+    OCI_INDEX, OCI_LAYOUT_FILE, OCI_MANIFEST,
+};
+use crate::ofs::layer::Layer;
+use crate::ofs::ofs::OverlayFs;
+use auth::load_credentials;
+use client::Client;
+use log::{info, trace, warn};
+use reference::Reference;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+const REF_NAME_ANNOTATION: &str = "org.opencontainers.image.ref.name";
+
+// Copies everything read from the registry into the blob file
+struct TeeReader<R: Read, W: Write> {
+    reader: R,
+    writer: W,
+}
+
+impl<R: Read, W: Write> Read for TeeReader<R, W> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.reader.read(buf)?;
+        self.writer.write_all(&buf[..n])?;
+        Ok(n)
+    }
+}
+
+fn write_blob(dir: &str, digest: &str, content: &[u8]) -> Result<()> {
+    let path = Path::new(dir).join(blob_path(digest)?);
+    fs::create_dir_all(path.parent().unwrap()).context(dir)?;
+    fs::write(&path, content).context(format!("Failed to write {:?}", path))
+}
+
+// The digest of the manifest (or index) the reference currently points to
+pub fn resolve_digest(image: &str) -> Result<String> {
+    let reference = Reference::parse(image);
+    if let Some(digest) = &reference.digest {
+        return Ok(digest.clone());
+    }
+
+    let mut client = Client::new(&reference, load_credentials(&reference.registry));
+    let (content, _) = client.get_manifest(reference.reference())?;
+    Ok(sha256_digest(&content))
+}
+
+// The platforms of a multi-arch image, attestation manifests are left out
+pub fn platforms(image: &str) -> Result<Vec<Platform>> {
+    let reference = Reference::parse(image);
+    let mut client = Client::new(&reference, load_credentials(&reference.registry));
+    let (content, media_type) = client.get_manifest(reference.reference())?;
+
+    if !is_index_media_type(&media_type) {
+        return Err(Error::Invalid(format!(
+            "{} is not a multi-platform image",
+            reference.name()
+        )));
+    }
+    let index = parse_index(&content, &reference)?;
+    Ok(index.platforms())
+}
+
+pub fn pull(
+    image: &str,
+    platform: Option<&Platform>,
+    dest: &str,
+    ofs: &mut OverlayFs,
+    hash: bool,
+) -> Result<()> {
+    let reference = Reference::parse(image);
+    info!("Pulling {} from {}", reference.name(), reference.registry);
+
+    let mut client = Client::new(&reference, load_credentials(&reference.registry));
+    pull_image(&mut client, &reference, platform, dest, ofs, hash)
+}
+
+fn parse_index(content: &[u8], reference: &Reference) -> Result<ImageIndex> {
+    serde_json::from_slice(content).map_err(|e| {
+        Error::Registry(format!(
+            "Invalid image index for {}: {}",
+            reference.name(),
+            e
+        ))
+    })
+}
+
+fn pull_image(
+    client: &mut Client,
+    reference: &Reference,
+    platform: Option<&Platform>,
+    dest: &str,
+    ofs: &mut OverlayFs,
+    hash: bool,
+) -> Result<()> {
+    let (mut content, mut media_type) = client.get_manifest(reference.reference())?;
+    if let Some(digest) = &reference.digest {
+        verify_digest("manifest", &sha256_digest(&content), digest)?;
+    }
+
+    if is_index_media_type(&media_type) {
+        let index = parse_index(&content, reference)?;
+        let desc = match platform {
+            Some(p) => index.manifest_for(p).ok_or_else(|| {
+                Error::ImageNotFound(format!("No {} image for {}", p, reference.name()))
+            })?,
+            None => index.default_manifest().ok_or_else(|| {
+                Error::Registry(format!("Empty image index for {}", reference.name()))
+            })?,
+        };
+
+        trace!("Selected manifest {} from index", desc.digest);
+        let (c, m) = client.get_manifest(&desc.digest)?;
+        verify_digest("manifest", &sha256_digest(&c), &desc.digest)?;
+        content = c;
+        media_type = m;
+    }
+
+    let manifest: ImageManifest = serde_json::from_slice(&content).map_err(|e| {
+        Error::Registry(format!("Invalid manifest for {}: {}", reference.name(), e))
+    })?;
+    let manifest_digest = sha256_digest(&content);
+    write_blob(dest, &manifest_digest, &content)?;
+
+    let mut config = vec![];
+    client
+        .get_blob(&manifest.config.digest)?
+        .read_to_end(&mut config)
+        .context("Failed to read image config")?;
+    verify_digest(
+        "image config",
+        &sha256_digest(&config),
+        &manifest.config.digest,
+    )?;
+    write_blob(dest, &manifest.config.digest, &config)?;
+
+    let config = match ImageConfig::from_slice(&config) {
+        Ok(config) => Some(config),
+        Err(e) => {
+            warn!("No build history for {}: {}", reference.name(), e);
+            None
+        }
+    };
+    let diff_ids = config.as_ref().map(|c| &c.rootfs.diff_ids);
+    if let Some(diff_ids) = diff_ids {
+        if diff_ids.len() != manifest.layers.len() {
+            return Err(Error::Registry(format!(
+                "Image config lists {} layers, the manifest {}",
+                diff_ids.len(),
+                manifest.layers.len()
+            )));
+        }
+    }
+
+    for (i, layer) in manifest.layers.iter().enumerate() {
+        let path = Path::new(dest).join(blob_path(&layer.digest)?);
+        trace!("Streaming layer {} to {:?}", layer.digest, path);
+        fs::create_dir_all(path.parent().unwrap()).context(dest)?;
+
+        let file = File::create(&path).context(format!("Failed to create {:?}", path))?;
+        let tee = TeeReader {
+            reader: client.get_blob(&layer.digest)?,
+            writer: file,
+        };
+
+        let parsed = parse_blob(tee, hash).map_err(|e| Error::corrupt(&layer.digest, e))?;
+        let diff_id = diff_ids.map(|d| d[i].as_str());
+        parsed
+            .digests
+            .verify(&layer.digest, Some(&layer.digest), diff_id)?;
+
+        let layer = Layer::new(i, path.to_string_lossy().into_owned());
+        apply_layer(ofs, &parsed.entries, &layer.layer_id)?;
+        ofs.add_layer(layer);
+    }
+
+    if let Some(config) = &config {
+        ofs.set_history(&config.history);
+    }
+
+    let mut annotations = HashMap::new();
+    annotations.insert(REF_NAME_ANNOTATION.to_string(), reference.name());
+    let index = ImageIndex {
+        schema_version: 2,
+        media_type: Some(OCI_INDEX.to_string()),
+        manifests: vec![Descriptor {
+            media_type: if media_type.is_empty() {
+                OCI_MANIFEST.to_string()
+            } else {
+                media_type
+            },
+            digest: manifest_digest,
+            size: content.len() as u64,
+            annotations: Some(annotations),
+            platform: None,
+        }],
+    };
+
+    let index = serde_json::to_string(&index).unwrap();
+    fs::write(Path::new(dest).join(INDEX_FILE), index).context(dest)?;
+    fs::write(
+        Path::new(dest).join(OCI_LAYOUT_FILE),
+        r#"{"imageLayoutVersion":"1.0.0"}"#,
+    )
+    .context(dest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::auth::Credentials;
+    use super::client::Client;
+    use super::pull_image;
+    use super::reference::Reference;
+    use crate::error::Error;
+    use crate::image::manifest::Manifest;
+    use crate::image::oci::{Platform, OCI_INDEX, OCI_MANIFEST};
+    use crate::image::store::DirStore;
+    use crate::ofs::ofs::OverlayFs;
+    use std::fs;
+    use std::thread;
+    use tiny_http::{Header, Response, Server};
+
+    const LAYOUT: &str = "test_files/oci";
+    const TOKEN: &str = "Bearer test-token";
+
+    // Serves test_files/oci like a registry:2 instance behind a token server
+    fn start_registry() -> String {
+        let server = Server::http("127.0.0.1:0").unwrap();
+        let addr = server.server_addr().to_string();
+        let realm = format!("http://{}/token", addr);
+
+        thread::spawn(move || {
+            for request in server.incoming_requests() {
+                let url = request.url().to_string();
+                let auth = request
+                    .headers()
+                    .iter()
+                    .find(|h| h.field.equiv("Authorization"))
+                    .map(|h| h.value.to_string());
+
+                let response = if url.starts_with("/token") {
+                    if auth.as_deref() == Some("Basic dXNlcjpwYXNz") {
+                        Response::from_string(r#"{"token":"test-token"}"#)
+                    } else {
+                        Response::from_string("denied").with_status_code(401)
+                    }
+                } else if auth.as_deref() != Some(TOKEN) {
+                    let challenge = format!(
+                        r#"Bearer realm="{}",service="test",scope="repository:cake/test:pull""#,
+                        realm
+                    );
+                    Response::from_string("unauthorized")
+                        .with_status_code(401)
+                        .with_header(Header::from_bytes("WWW-Authenticate", challenge).unwrap())
+                } else if url == "/v2/cake/test/manifests/latest" {
+                    Response::from_data(fs::read(format!("{}/index.json", LAYOUT)).unwrap())
+                        .with_header(Header::from_bytes("Content-Type", OCI_INDEX).unwrap())
+                } else if let Some(digest) = url
+                    .strip_prefix("/v2/cake/test/manifests/sha256:")
+                    .or_else(|| url.strip_prefix("/v2/cake/test/blobs/sha256:"))
+                {
+                    match fs::read(format!("{}/blobs/sha256/{}", LAYOUT, digest)) {
+                        Ok(data) => Response::from_data(data)
+                            .with_header(Header::from_bytes("Content-Type", OCI_MANIFEST).unwrap()),
+                        Err(_) => Response::from_string("not found").with_status_code(404),
+                    }
+                } else {
+                    Response::from_string("not found").with_status_code(404)
+                };
+                let _ = request.respond(response);
+            }
+        });
+        addr
+    }
+
+    #[test]
+    fn pulls_image_from_registry() {
+        let addr = start_registry();
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().to_str().unwrap();
+
+        let reference = Reference::parse(&format!("{}/cake/test", addr));
+        let credentials = Credentials {
+            username: "user".to_string(),
+            password: "pass".to_string(),
+        };
+        let mut client = Client::new(&reference, Some(credentials));
+        let mut ofs = OverlayFs::new();
+        pull_image(&mut client, &reference, None, dest, &mut ofs, false).unwrap();
+
+        assert_eq!(ofs.get_file("/bin/busybox").unwrap().versions.len(), 1);
+        let layer = ofs.get_layer("layer1").unwrap();
+        assert_eq!(layer.index, 2);
+        assert!(layer
+            .created_by
+            .as_ref()
+            .unwrap()
+            .contains("rm /etc/os-release"));
+        assert!(ofs.get_layer("empty1").unwrap().empty_layer);
+
+        let manifest = Manifest::for_store(&DirStore::new(dest)).unwrap();
+        assert_eq!(manifest.layers.len(), 2);
+        for layer in manifest.layers.iter() {
+            assert_eq!(
+                fs::read(format!("{}/{}", dest, layer)).unwrap(),
+                fs::read(format!("{}/{}", LAYOUT, layer)).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn fails_without_credentials() {
+        let addr = start_registry();
+        let dir = tempfile::tempdir().unwrap();
+
+        let reference = Reference::parse(&format!("{}/cake/test", addr));
+        let mut client = Client::new(&reference, None);
+        let mut ofs = OverlayFs::new();
+        let result = pull_image(
+            &mut client,
+            &reference,
+            None,
+            dir.path().to_str().unwrap(),
+            &mut ofs,
+            false,
+        );
+        assert!(
+            matches!(result, Err(Error::Registry(m)) if m.starts_with("Failed to get token from"))
+        );
+    }
+
+    #[test]
+    fn pulls_selected_platform() {
+        let addr = start_registry();
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().to_str().unwrap();
+
+        let reference = Reference::parse(&format!("{}/cake/test", addr));
+        let credentials = Credentials {
+            username: "user".to_string(),
+            password: "pass".to_string(),
+        };
+        let mut client = Client::new(&reference, Some(credentials));
+        let mut ofs = OverlayFs::new();
+
+        let arm = Platform::parse("linux/arm64").unwrap();
+        let result = pull_image(&mut client, &reference, Some(&arm), dest, &mut ofs, false);
+        assert!(
+            matches!(result, Err(Error::ImageNotFound(m)) if m.starts_with("No linux/arm64 image"))
+        );
+
+        let amd = Platform::parse("linux/amd64").unwrap();
+        pull_image(&mut client, &reference, Some(&amd), dest, &mut ofs, false).unwrap();
+        assert!(ofs.get_layer("layer1").is_some());
+    }
+}
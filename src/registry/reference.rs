@@ -0,0 +1,100 @@
+// Parses image references the way the docker cli does, e.g.
+// alpine:3 -> registry-1.docker.io/library/alpine:3
+pub const DOCKER_HUB: &str = "registry-1.docker.io";
+const DEFAULT_TAG: &str = "latest";
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Reference {
+    pub registry: String,
+    pub repository: String,
+    pub tag: String,
+    pub digest: Option<String>,
+}
+
+fn is_registry(component: &str) -> bool {
+    component.contains('.') || component.contains(':') || component == "localhost"
+}
+
+impl Reference {
+    pub fn parse(image: &str) -> Reference {
+        let (name, digest) = match image.split_once('@') {
+            Some((name, digest)) => (name, Some(digest.to_string())),
+            None => (image, None),
+        };
+
+        let (name, tag) = match name.rfind(':') {
+            Some(idx) if !name[idx..].contains('/') => (&name[..idx], &name[idx + 1..]),
+            _ => (name, DEFAULT_TAG),
+        };
+
+        let (registry, repository) = match name.split_once('/') {
+            Some((first, rest)) if is_registry(first) => (first.to_string(), rest.to_string()),
+            _ => (DOCKER_HUB.to_string(), name.to_string()),
+        };
+
+        let registry = match registry.as_str() {
+            "docker.io" | "index.docker.io" => DOCKER_HUB.to_string(),
+            _ => registry,
+        };
+
+        let repository = if registry == DOCKER_HUB && !repository.contains('/') {
+            format!("library/{}", repository)
+        } else {
+            repository
+        };
+
+        Reference {
+            registry,
+            repository,
+            tag: tag.to_string(),
+            digest,
+        }
+    }
+
+    // Digest wins over the tag when both are given
+    pub fn reference(&self) -> &str {
+        self.digest.as_deref().unwrap_or(&self.tag)
+    }
+
+    pub fn name(&self) -> String {
+        format!("{}/{}:{}", self.registry, self.repository, self.tag)
+    }
+
+    pub fn is_local(&self) -> bool {
+        let host = self.registry.split(':').next().unwrap_or("");
+        host == "localhost" || host == "127.0.0.1"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::registry::reference::{Reference, DOCKER_HUB};
+
+    #[test]
+    fn parses_docker_hub_references() {
+        let r = Reference::parse("alpine:3");
+        assert_eq!(r.registry, DOCKER_HUB);
+        assert_eq!(r.repository, "library/alpine");
+        assert_eq!(r.reference(), "3");
+
+        let r = Reference::parse("docker.io/grafana/grafana");
+        assert_eq!(r.registry, DOCKER_HUB);
+        assert_eq!(r.repository, "grafana/grafana");
+        assert_eq!(r.reference(), "latest");
+    }
+
+    #[test]
+    fn parses_registry_with_port_and_digest() {
+        let r = Reference::parse("localhost:5000/org/app:1.2@sha256:abcd");
+        assert_eq!(r.registry, "localhost:5000");
+        assert_eq!(r.repository, "org/app");
+        assert_eq!(r.tag, "1.2");
+        assert_eq!(r.reference(), "sha256:abcd");
+        assert!(r.is_local());
+
+        let r = Reference::parse("ghcr.io/org/app");
+        assert_eq!(r.registry, "ghcr.io");
+        assert_eq!(r.repository, "org/app");
+        assert!(!r.is_local());
+    }
+}
@@ -0,0 +1,443 @@
+// State of the interactive view, the drawing is in ui.rs
+use crate::analysis::layers::{layer_changes, ChangeSummary, LayerChange};
+use crate::analysis::report::AnalysisReport;
+use crate::docker::container::ChangeKind;
+use crate::ofs::directory::{DirEntry, Node};
+use crate::ofs::layer::Layer;
+use ratatui::crossterm::event::KeyCode;
+use ratatui::widgets::ListState;
+use std::collections::{HashMap, HashSet};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Focus {
+    Layers,
+    Tree,
+    Filter,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Sort {
+    Name,
+    Size,
+}
+
+// One line of the file tree
+#[derive(Clone)]
+pub struct Row {
+    pub depth: usize,
+    pub name: String,
+    pub path: String,
+    pub dir: bool,
+    pub size: u64,
+    pub expanded: bool,
+    pub change: Option<ChangeKind>,
+    // A directory holding files the selected layer changed
+    pub changed: bool,
+    pub waste: bool,
+    // Index after the last row below a directory in the whole tree
+    end: usize,
+}
+
+pub struct App<'a> {
+    report: &'a AnalysisReport,
+    pub layers: Vec<&'a Layer>,
+    pub summaries: Vec<ChangeSummary>,
+    changes: HashMap<&'a str, Vec<LayerChange>>,
+    // Paths the report lists as wasted space
+    waste: HashSet<&'a str>,
+    expanded: HashSet<String>,
+    // Changes of the selected layer by path, with the bytes they added or removed
+    selected: HashMap<String, (ChangeKind, u64)>,
+    changed_dirs: HashSet<String>,
+    // Every directory open, only built again when the sort or the layer changes
+    all_rows: Vec<Row>,
+    pub focus: Focus,
+    pub sort: Sort,
+    pub waste_only: bool,
+    pub filter: String,
+    // 0 is the whole image, layer i is at i + 1
+    pub layer: usize,
+    pub rows: Vec<Row>,
+    pub tree: ListState,
+    pub quit: bool,
+}
+
+impl<'a> App<'a> {
+    pub fn new(report: &'a AnalysisReport) -> App<'a> {
+        let layers: Vec<&Layer> = report
+            .ofs
+            .get_layers()
+            .into_iter()
+            .filter(|l| !l.empty_layer)
+            .collect();
+        let changes = layer_changes(&report.ofs);
+        let summaries = layers
+            .iter()
+            .map(|l| {
+                changes
+                    .get(l.layer_id.as_str())
+                    .map(|c| ChangeSummary::new(c))
+                    .unwrap_or_default()
+            })
+            .collect();
+
+        let mut waste: HashSet<&str> = HashSet::new();
+        waste.extend(report.dup_files.iter().map(|i| i.path.as_str()));
+        waste.extend(report.metadata_rewrites.iter().map(|i| i.path.as_str()));
+        for same in report.same_content.iter() {
+            waste.extend(same.paths.iter().map(|p| p.as_str()));
+        }
+
+        let mut app = App {
+            report,
+            layers,
+            summaries,
+            changes,
+            waste,
+            expanded: HashSet::new(),
+            selected: HashMap::new(),
+            changed_dirs: HashSet::new(),
+            all_rows: vec![],
+            focus: Focus::Layers,
+            sort: Sort::Name,
+            waste_only: false,
+            filter: String::new(),
+            layer: 0,
+            rows: vec![],
+            tree: ListState::default(),
+            quit: false,
+        };
+        app.rebuild();
+        app
+    }
+
+    pub fn report(&self) -> &'a AnalysisReport {
+        self.report
+    }
+
+    pub fn selected_layer(&self) -> Option<(&'a Layer, &ChangeSummary)> {
+        let i = self.layer.checked_sub(1)?;
+        Some((self.layers[i], &self.summaries[i]))
+    }
+
+    pub fn row(&self) -> usize {
+        self.tree.selected().unwrap_or(0)
+    }
+
+    fn select_layer(&mut self, layer: usize) {
+        self.layer = layer;
+        self.selected.clear();
+        self.changed_dirs.clear();
+        if let Some((l, _)) = self.selected_layer() {
+            for c in self.changes.get(l.layer_id.as_str()).into_iter().flatten() {
+                let path = c.path.trim_start_matches('/');
+                let mut dir = path;
+                while let Some((parent, _)) = dir.rsplit_once('/') {
+                    self.changed_dirs.insert(parent.to_string());
+                    dir = parent;
+                }
+                self.selected.insert(path.to_string(), (c.kind, c.size));
+            }
+        }
+        self.rebuild();
+    }
+
+    // Filters and the waste toggle open every directory with a match
+    fn searching(&self) -> bool {
+        !self.filter.is_empty() || self.waste_only
+    }
+
+    fn rebuild(&mut self) {
+        let mut rows = vec![];
+        if let Some(root) = self.report.ofs.get_dir("/") {
+            self.add_rows(root, "", 0, &mut rows);
+        }
+        self.all_rows = rows;
+        self.refresh();
+    }
+
+    // Picks the visible rows out of the whole tree
+    pub fn refresh(&mut self) {
+        let all = &self.all_rows;
+        let mut rows = vec![];
+        if self.searching() {
+            let filter = self.filter.to_lowercase();
+            // Walking backwards, a directory is kept when a file below it matched
+            let mut keep = vec![false; all.len()];
+            let mut next_match = all.len();
+            for (i, r) in all.iter().enumerate().rev() {
+                keep[i] = if r.dir {
+                    next_match < r.end
+                } else {
+                    (!self.waste_only || r.waste)
+                        && (filter.is_empty() || r.path.to_lowercase().contains(&filter))
+                };
+                if keep[i] && !r.dir {
+                    next_match = i;
+                }
+            }
+            rows.extend(
+                all.iter()
+                    .zip(keep)
+                    .filter(|(_, keep)| *keep)
+                    .map(|(r, _)| Row {
+                        expanded: r.dir,
+                        ..r.clone()
+                    }),
+            );
+        } else {
+            let mut i = 0;
+            while let Some(r) = all.get(i) {
+                let expanded = r.dir && self.expanded.contains(&r.path);
+                rows.push(Row {
+                    expanded,
+                    ..r.clone()
+                });
+                i = if r.dir && !expanded { r.end } else { i + 1 };
+            }
+        }
+        self.rows = rows;
+        let row = self.row().min(self.rows.len().saturating_sub(1));
+        self.tree.select(Some(row));
+    }
+
+    fn children(&self, dir: DirEntry<'a>) -> Vec<Node<'a>> {
+        let mut children = dir.children();
+        children.sort_by(|a, b| a.name().cmp(b.name()));
+        if self.sort == Sort::Size {
+            children.sort_by_key(|c| std::cmp::Reverse(c.size()));
+        }
+        children
+    }
+
+    fn add_rows(&self, dir: DirEntry<'a>, path: &str, depth: usize, rows: &mut Vec<Row>) {
+        for child in self.children(dir) {
+            let child_path = format!("{}{}", path, child.name());
+            match child {
+                Node::Dir(d) => {
+                    let i = rows.len();
+                    rows.push(Row {
+                        depth,
+                        name: d.name().to_string(),
+                        dir: true,
+                        size: d.size(),
+                        expanded: true,
+                        change: None,
+                        changed: self.changed_dirs.contains(&child_path),
+                        waste: false,
+                        end: 0,
+                        path: child_path.clone(),
+                    });
+                    self.add_rows(d, &format!("{}/", child_path), depth + 1, rows);
+                    rows[i].end = rows.len();
+                }
+                Node::File(f) => {
+                    let change = self.selected.get(&child_path).copied();
+                    // Deleted files are only shown for the layer which changed them
+                    if f.is_deleted() && change.is_none() {
+                        continue;
+                    }
+                    let size = match (change, f.versions.last()) {
+                        (Some((_, size)), _) => size,
+                        (None, Some(v)) => v.size,
+                        (None, None) => f.size,
+                    };
+                    rows.push(Row {
+                        depth,
+                        name: f.name().to_string(),
+                        dir: false,
+                        size,
+                        expanded: false,
+                        change: change.map(|c| c.0),
+                        changed: false,
+                        waste: self.waste.contains(child_path.as_str()),
+                        end: rows.len() + 1,
+                        path: child_path,
+                    });
+                }
+            }
+        }
+    }
+
+    fn toggle_dir(&mut self, open: bool) {
+        let row = self.row();
+        let (path, dir, depth) = match self.rows.get(row) {
+            Some(r) => (r.path.clone(), r.dir, r.depth),
+            None => return,
+        };
+        if dir && open {
+            if !self.expanded.remove(&path) {
+                self.expanded.insert(path);
+            }
+        } else if dir && self.expanded.contains(&path) {
+            self.expanded.remove(&path);
+        } else if let Some(parent) = self.rows[..row].iter().rposition(|r| r.depth + 1 == depth) {
+            // Collapsing a file or a closed directory goes to its parent
+            self.tree.select(Some(parent));
+            return;
+        }
+        self.refresh();
+    }
+
+    fn move_by(&mut self, delta: isize) {
+        match self.focus {
+            Focus::Layers => {
+                let last = self.layers.len() as isize;
+                let layer = (self.layer as isize + delta).clamp(0, last);
+                self.select_layer(layer as usize);
+            }
+            _ => {
+                let last = self.rows.len().saturating_sub(1) as isize;
+                let row = (self.row() as isize + delta).clamp(0, last);
+                self.tree.select(Some(row as usize));
+            }
+        }
+    }
+
+    pub fn on_key(&mut self, key: KeyCode) {
+        if self.focus == Focus::Filter {
+            match key {
+                KeyCode::Enter => self.focus = Focus::Tree,
+                KeyCode::Esc => {
+                    self.filter.clear();
+                    self.focus = Focus::Tree;
+                }
+                KeyCode::Backspace => {
+                    self.filter.pop();
+                }
+                KeyCode::Char(c) => self.filter.push(c),
+                _ => return,
+            }
+            self.refresh();
+            return;
+        }
+
+        match key {
+            KeyCode::Char('q') | KeyCode::Esc => self.quit = true,
+            KeyCode::Tab => {
+                self.focus = match self.focus {
+                    Focus::Layers => Focus::Tree,
+                    _ => Focus::Layers,
+                }
+            }
+            KeyCode::Up | KeyCode::Char('k') => self.move_by(-1),
+            KeyCode::Down | KeyCode::Char('j') => self.move_by(1),
+            KeyCode::PageUp => self.move_by(-20),
+            KeyCode::PageDown => self.move_by(20),
+            KeyCode::Enter | KeyCode::Char(' ') | KeyCode::Right if self.focus == Focus::Tree => {
+                self.toggle_dir(true)
+            }
+            KeyCode::Left if self.focus == Focus::Tree => self.toggle_dir(false),
+            KeyCode::Char('/') => self.focus = Focus::Filter,
+            KeyCode::Char('s') => {
+                self.sort = match self.sort {
+                    Sort::Name => Sort::Size,
+                    Sort::Size => Sort::Name,
+                };
+                self.rebuild();
+            }
+            KeyCode::Char('w') => {
+                self.waste_only = !self.waste_only;
+                self.refresh();
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::analysis::report::AnalysisReport;
+    use crate::docker::container::ChangeKind;
+    use crate::ofs::layer::Layer;
+    use crate::ofs::ofs::OverlayFs;
+    use crate::tui::app::{App, Focus};
+    use ratatui::crossterm::event::KeyCode;
+
+    fn paths(app: &App) -> Vec<String> {
+        app.rows.iter().map(|r| r.path.clone()).collect()
+    }
+
+    #[test]
+    fn browses_layers_and_files() {
+        let mut ofs = OverlayFs::new();
+        ofs.add_file("etc/motd", 10, "layer0");
+        ofs.add_file("usr/bin/git", 300, "layer0");
+        ofs.add_file("usr/bin/.wh.git", 0, "layer1");
+        ofs.add_file("usr/lib/libz.so", 100, "layer1");
+        ofs.add_file("etc/motd", 20, "layer1");
+        ofs.add_layer(Layer::new(0, String::new()));
+        ofs.add_layer(Layer::new(1, String::new()));
+        ofs.update_sizes();
+        let report = AnalysisReport::create_analysis_report(ofs, "app", vec![]);
+
+        let mut app = App::new(&report);
+        assert_eq!(paths(&app), vec!["etc", "usr"]);
+
+        // The second layer deleted git, it only shows while that layer is selected
+        app.on_key(KeyCode::Down);
+        app.on_key(KeyCode::Down);
+        app.on_key(KeyCode::Tab);
+        app.on_key(KeyCode::Down);
+        app.on_key(KeyCode::Enter);
+        app.on_key(KeyCode::Down);
+        app.on_key(KeyCode::Enter);
+        assert_eq!(
+            paths(&app),
+            vec!["etc", "usr", "usr/bin", "usr/bin/git", "usr/lib"]
+        );
+        assert_eq!(app.rows[3].change, Some(ChangeKind::Deleted));
+        assert!(app.rows[1].changed);
+
+        // Files written again or deleted by a later layer are waste
+        app.on_key(KeyCode::Char('w'));
+        assert_eq!(
+            paths(&app),
+            vec!["etc", "etc/motd", "usr", "usr/bin", "usr/bin/git"]
+        );
+        assert_eq!(app.rows[1].change, Some(ChangeKind::Modified));
+        app.on_key(KeyCode::Char('w'));
+
+        app.on_key(KeyCode::Char('/'));
+        assert!(app.focus == Focus::Filter);
+        for c in "libz".chars() {
+            app.on_key(KeyCode::Char(c));
+        }
+        assert_eq!(paths(&app), vec!["usr", "usr/lib", "usr/lib/libz.so"]);
+        app.on_key(KeyCode::Esc);
+        assert_eq!(
+            paths(&app),
+            vec!["etc", "usr", "usr/bin", "usr/bin/git", "usr/lib"]
+        );
+    }
+
+    #[test]
+    fn builds_the_tree_again_only_for_sort_and_layer() {
+        let mut ofs = OverlayFs::new();
+        ofs.add_file("etc/motd", 10, "layer0");
+        ofs.add_file("usr/bin/git", 300, "layer0");
+        ofs.add_file("usr/lib/libz.so", 100, "layer0");
+        ofs.add_layer(Layer::new(0, String::new()));
+        ofs.update_sizes();
+        let report = AnalysisReport::create_analysis_report(ofs, "app", vec![]);
+
+        let mut app = App::new(&report);
+        let tree = app.all_rows.as_ptr();
+        app.on_key(KeyCode::Tab);
+        app.on_key(KeyCode::Down);
+        app.on_key(KeyCode::Enter);
+        assert_eq!(paths(&app), vec!["etc", "usr", "usr/bin", "usr/lib"]);
+        app.on_key(KeyCode::Char('/'));
+        app.on_key(KeyCode::Char('g'));
+        assert_eq!(paths(&app), vec!["usr", "usr/bin", "usr/bin/git"]);
+        app.on_key(KeyCode::Esc);
+        app.on_key(KeyCode::Left);
+        assert_eq!(paths(&app), vec!["etc", "usr"]);
+        assert_eq!(app.all_rows.as_ptr(), tree);
+
+        // Open directories stay open in the new order
+        app.on_key(KeyCode::Enter);
+        app.on_key(KeyCode::Char('s'));
+        assert_eq!(paths(&app), vec!["usr", "usr/bin", "usr/lib", "etc"]);
+    }
+}
@@ -0,0 +1,37 @@
+// Interactive view of an analyzed image: its layers next to the merged file
+// tree, with the files the selected layer added, modified and removed marked.
+use crate::analysis::report::AnalysisReport;
+use crate::error::{Context, Error, Result};
+use app::App;
+use ratatui::crossterm::event::{self, Event, KeyEventKind};
+use ratatui::DefaultTerminal;
+use std::io::{self, IsTerminal};
+
+mod app;
+mod ui;
+
+pub fn run(report: &AnalysisReport) -> Result<()> {
+    if !io::stdout().is_terminal() {
+        return Err(Error::Invalid(
+            "The interactive view needs a terminal".to_string(),
+        ));
+    }
+    let mut app = App::new(report);
+    let mut terminal = ratatui::try_init().context("Failed to set up the terminal")?;
+    let result = event_loop(&mut terminal, &mut app);
+    ratatui::restore();
+    result.context("Failed to draw the interactive view")
+}
+
+fn event_loop(terminal: &mut DefaultTerminal, app: &mut App) -> io::Result<()> {
+    while !app.quit {
+        terminal.draw(|frame| ui::draw(frame, app))?;
+        if let Event::Key(key) = event::read()? {
+            // Windows also reports releases
+            if key.kind == KeyEventKind::Press {
+                app.on_key(key.code);
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,161 @@
+use super::app::{App, Focus, Row, Sort};
+use crate::docker::container::ChangeKind;
+use crate::ofs::utils::size_human;
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, List, ListItem, ListState, Paragraph};
+use ratatui::Frame;
+
+const HELP: &str =
+    "tab switch pane  ↑↓ move  enter open  ← close  s sort  w waste only  / filter  q quit";
+
+fn pane(title: String, focused: bool) -> Block<'static> {
+    let style = if focused {
+        Style::default().fg(Color::Cyan)
+    } else {
+        Style::default()
+    };
+    Block::bordered().title(title).border_style(style)
+}
+
+fn change_style(kind: ChangeKind) -> Style {
+    match kind {
+        ChangeKind::Added => Style::default().fg(Color::Green),
+        ChangeKind::Modified => Style::default().fg(Color::Yellow),
+        ChangeKind::Deleted => Style::default()
+            .fg(Color::Red)
+            .add_modifier(Modifier::CROSSED_OUT),
+    }
+}
+
+fn marker(row: &Row) -> &'static str {
+    match row.change {
+        Some(ChangeKind::Added) => "A ",
+        Some(ChangeKind::Modified) => "C ",
+        Some(ChangeKind::Deleted) => "D ",
+        None if row.waste => "! ",
+        None => "  ",
+    }
+}
+
+fn tree_line(row: &Row) -> Line<'_> {
+    let indent = "  ".repeat(row.depth);
+    let (icon, style) = match (row.dir, row.change) {
+        (true, _) => {
+            let icon = if row.expanded { "▾ " } else { "▸ " };
+            let style = if row.changed {
+                Style::default().add_modifier(Modifier::BOLD)
+            } else {
+                Style::default()
+            };
+            (icon, style.fg(Color::Blue))
+        }
+        (false, Some(kind)) => ("  ", change_style(kind)),
+        (false, None) if row.waste => ("  ", Style::default().fg(Color::Magenta)),
+        (false, None) => ("  ", Style::default()),
+    };
+    Line::from(vec![
+        Span::raw(format!("{:>10} ", size_human(row.size))),
+        Span::styled(marker(row), style),
+        Span::raw(indent),
+        Span::styled(format!("{}{}", icon, row.name), style),
+    ])
+}
+
+fn draw_layers(frame: &mut Frame, app: &App, area: ratatui::layout::Rect) {
+    let report = app.report();
+    let mut items = vec![ListItem::new(format!(
+        "{:>5}  {:>10}  whole image, {} wasted",
+        "all",
+        size_human(report.total_space),
+        size_human(report.wasted_space)
+    ))];
+    for (l, summary) in app.layers.iter().zip(app.summaries.iter()) {
+        items.push(ListItem::new(format!(
+            "{:>5}  {:>10}  {}",
+            l.index,
+            size_human(summary.added + summary.modified),
+            l.instruction()
+        )));
+    }
+    let list = List::new(items)
+        .block(pane("Layers".to_string(), app.focus == Focus::Layers))
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    let mut state = ListState::default().with_selected(Some(app.layer));
+    frame.render_stateful_widget(list, area, &mut state);
+}
+
+fn draw_details(frame: &mut Frame, app: &App, area: ratatui::layout::Rect) {
+    let lines = match app.selected_layer() {
+        Some((layer, summary)) => vec![
+            Line::from(layer.instruction()),
+            Line::from(format!("Files: {}", summary.files)),
+            Line::styled(
+                format!("Added: {}", size_human(summary.added)),
+                change_style(ChangeKind::Added),
+            ),
+            Line::styled(
+                format!("Modified: {}", size_human(summary.modified)),
+                change_style(ChangeKind::Modified),
+            ),
+            Line::styled(
+                format!("Removed: {}", size_human(summary.removed)),
+                Style::default().fg(Color::Red),
+            ),
+        ],
+        None => {
+            let report = app.report();
+            vec![
+                Line::from(report.image.clone()),
+                Line::from(format!("Efficiency score: {} %", report.score)),
+                Line::from(format!("Total size: {}", size_human(report.total_space))),
+                Line::from(format!("Wasted space: {}", size_human(report.wasted_space))),
+            ]
+        }
+    };
+    frame.render_widget(
+        Paragraph::new(lines).block(Block::bordered().title("Details")),
+        area,
+    );
+}
+
+pub fn draw(frame: &mut Frame, app: &mut App) {
+    let [main, status] =
+        Layout::vertical([Constraint::Min(3), Constraint::Length(1)]).areas(frame.area());
+    let [left, tree] =
+        Layout::horizontal([Constraint::Percentage(40), Constraint::Percentage(60)]).areas(main);
+    let [layers, details] =
+        Layout::vertical([Constraint::Min(3), Constraint::Length(7)]).areas(left);
+
+    draw_layers(frame, app, layers);
+    draw_details(frame, app, details);
+
+    let sort = match app.sort {
+        Sort::Name => "by name",
+        Sort::Size => "by size",
+    };
+    let mut title = format!("Files {}", sort);
+    if app.waste_only {
+        title.push_str(", waste only");
+    }
+    let items: Vec<ListItem> = app
+        .rows
+        .iter()
+        .map(|r| ListItem::new(tree_line(r)))
+        .collect();
+    let list = List::new(items)
+        .block(pane(title, app.focus != Focus::Layers))
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    frame.render_stateful_widget(list, tree, &mut app.tree);
+
+    let line = if app.focus == Focus::Filter || !app.filter.is_empty() {
+        Line::from(vec![
+            Span::styled("Filter: ", Style::default().fg(Color::Cyan)),
+            Span::raw(app.filter.clone()),
+        ])
+    } else {
+        Line::from(HELP)
+    };
+    frame.render_widget(Paragraph::new(line), status);
+}
@@ -0,0 +1,282 @@
+// Everything cake writes lives under $XDG_CACHE_HOME/cake, one directory per image digest:
+//   <digest>/info.json    image name and last use
+//   <digest>/report.cbor  the analysis report, see cache.rs
+//   <digest>/image.tar    saved image, read in place and removed once the report is written
+//   <digest>/image/       layers pulled from a registry, removed once the report is written
+use crate::ofs::utils::size_human;
+use crate::style::bold;
+use log::{trace, warn};
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tempfile::TempDir;
+
+const INFO_FILE: &str = "info.json";
+const REPORT_FILE: &str = "report.cbor";
+// Written by cake versions before the cache format was versioned
+const LEGACY_FILES: [&str; 2] = ["tree.json", "report.json"];
+const IMAGE_TAR: &str = "image.tar";
+const IMAGE_DIR: &str = "image";
+
+pub const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+#[derive(Serialize, Deserialize)]
+pub struct EntryInfo {
+    pub image: String,
+    pub digest: String,
+    pub last_used: u64,
+}
+
+pub struct Workspace {
+    root: PathBuf,
+    // Removes the root when the workspace is dropped
+    _temporary: Option<TempDir>,
+}
+
+pub struct Entry {
+    dir: PathBuf,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn cache_home() -> PathBuf {
+    match env::var_os("XDG_CACHE_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var_os("HOME").unwrap_or_else(|| ".".into())).join(".cache"),
+    }
+}
+
+fn dir_size(path: &Path) -> u64 {
+    let mut size = 0;
+    if let Ok(entries) = fs::read_dir(path) {
+        for e in entries.flatten() {
+            match e.metadata() {
+                Ok(m) if m.is_dir() => size += dir_size(&e.path()),
+                Ok(m) => size += m.len(),
+                Err(_) => {}
+            }
+        }
+    }
+    size
+}
+
+fn remove(path: &Path) {
+    let result = if path.is_dir() {
+        fs::remove_dir_all(path)
+    } else if path.exists() {
+        fs::remove_file(path)
+    } else {
+        Ok(())
+    };
+    if let Err(e) = result {
+        warn!("Failed to remove {:?}: {}", path, e);
+    }
+}
+
+impl Workspace {
+    pub fn new() -> Workspace {
+        Workspace::at(cache_home().join("cake"))
+    }
+
+    pub fn at(root: PathBuf) -> Workspace {
+        Workspace {
+            root,
+            _temporary: None,
+        }
+    }
+
+    // Used with --no-cache, removed again when dropped. Every call gets a new
+    // directory, nested temporary workspaces never share files.
+    pub fn temporary() -> io::Result<Workspace> {
+        let dir = tempfile::Builder::new().prefix("cake-").tempdir()?;
+        Ok(Workspace {
+            root: dir.path().to_path_buf(),
+            _temporary: Some(dir),
+        })
+    }
+
+    // Digests look like sha256:abc.., which is not a valid file name on every platform
+    pub fn entry(&self, digest: &str) -> io::Result<Entry> {
+        let dir = self.root.join(digest.replace(':', "-"));
+        fs::create_dir_all(&dir)?;
+        Ok(Entry { dir })
+    }
+
+    pub fn entries(&self) -> Vec<Entry> {
+        let mut entries = vec![];
+        if let Ok(dirs) = fs::read_dir(&self.root) {
+            for d in dirs.flatten() {
+                if d.path().join(INFO_FILE).exists() {
+                    entries.push(Entry { dir: d.path() });
+                }
+            }
+        }
+        entries.sort_by_key(|e| std::cmp::Reverse(e.info().map(|i| i.last_used).unwrap_or(0)));
+        entries
+    }
+
+    // Removes entries not used within max_age seconds and leftovers of interrupted runs
+    pub fn prune(&self, max_age: u64) -> Vec<Entry> {
+        let mut removed = vec![];
+        let cutoff = now().saturating_sub(max_age);
+        if let Ok(dirs) = fs::read_dir(&self.root) {
+            for d in dirs.flatten() {
+                let entry = Entry { dir: d.path() };
+                match entry.info() {
+                    Some(info) if info.last_used >= cutoff => entry.cleanup(),
+                    _ => {
+                        trace!("Pruning {:?}", entry.dir);
+                        remove(&entry.dir);
+                        removed.push(entry);
+                    }
+                }
+            }
+        }
+        removed
+    }
+
+    pub fn clear(&self) {
+        remove(&self.root);
+    }
+
+    pub fn show_entries(&self) {
+        println!("{}", bold(&format!("Cache: {}", self.root.display())));
+        println!(
+            "{:<19}  {:>10}  {:<12}  Image",
+            "Digest", "Size", "Last used"
+        );
+        for e in self.entries() {
+            if let Some(info) = e.info() {
+                let short: String = info.digest.chars().take(19).collect();
+                println!(
+                    "{:<19}  {:>10}  {:<12}  {}",
+                    short,
+                    size_human(e.size()),
+                    last_used_human(info.last_used),
+                    info.image
+                );
+            }
+        }
+    }
+}
+
+impl Entry {
+    pub fn image_tar(&self) -> PathBuf {
+        self.dir.join(IMAGE_TAR)
+    }
+
+    pub fn image_dir(&self) -> PathBuf {
+        self.dir.join(IMAGE_DIR)
+    }
+
+    pub fn report_file(&self) -> PathBuf {
+        self.dir.join(REPORT_FILE)
+    }
+
+    pub fn size(&self) -> u64 {
+        dir_size(&self.dir)
+    }
+
+    pub fn info(&self) -> Option<EntryInfo> {
+        let json = fs::read_to_string(self.dir.join(INFO_FILE)).ok()?;
+        serde_json::from_str(&json).ok()
+    }
+
+    // The digest the cached report must belong to
+    pub fn digest(&self) -> String {
+        self.info().map(|i| i.digest).unwrap_or_default()
+    }
+
+    pub fn touch(&self, image: &str, digest: &str) -> io::Result<()> {
+        let info = EntryInfo {
+            image: image.to_string(),
+            digest: digest.to_string(),
+            last_used: now(),
+        };
+        fs::write(self.dir.join(INFO_FILE), serde_json::to_string(&info)?)
+    }
+
+    // The saved image and layers are only needed until the report is written
+    pub fn cleanup(&self) {
+        remove(&self.image_tar());
+        remove(&self.image_dir());
+        for file in LEGACY_FILES {
+            remove(&self.dir.join(file));
+        }
+    }
+}
+
+pub fn last_used_human(last_used: u64) -> String {
+    let days = now().saturating_sub(last_used) / SECONDS_PER_DAY;
+    match days {
+        0 => "today".to_string(),
+        1 => "1 day ago".to_string(),
+        d => format!("{} days ago", d),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::workspace::{Workspace, SECONDS_PER_DAY};
+    use std::fs;
+
+    #[test]
+    fn keeps_entries_per_digest() {
+        let dir = tempfile::tempdir().unwrap();
+        let workspace = Workspace::at(dir.path().to_path_buf());
+
+        let entry = workspace.entry("sha256:abcd").unwrap();
+        entry.touch("ghcr.io/org/app:1.2", "sha256:abcd").unwrap();
+        assert!(dir.path().join("sha256-abcd/info.json").exists());
+
+        let entries = workspace.entries();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].info().unwrap().image, "ghcr.io/org/app:1.2");
+    }
+
+    #[test]
+    fn prunes_unused_entries_and_leftovers() {
+        let dir = tempfile::tempdir().unwrap();
+        let workspace = Workspace::at(dir.path().to_path_buf());
+
+        let used = workspace.entry("sha256:used").unwrap();
+        used.touch("app:1", "sha256:used").unwrap();
+        fs::create_dir_all(used.image_dir()).unwrap();
+        fs::write(used.image_tar(), "tar").unwrap();
+
+        let old = workspace.entry("sha256:old").unwrap();
+        let info = r#"{"image":"app:0","digest":"sha256:old","last_used":0}"#;
+        fs::write(dir.path().join("sha256-old/info.json"), info).unwrap();
+
+        let removed = workspace.prune(30 * SECONDS_PER_DAY);
+        assert_eq!(removed.len(), 1);
+        assert!(!old.image_dir().parent().unwrap().exists());
+        assert!(!used.image_dir().exists());
+        assert!(!used.image_tar().exists());
+        assert_eq!(workspace.entries().len(), 1);
+    }
+
+    #[test]
+    fn removes_temporary_workspaces_on_drop() {
+        let outer = Workspace::temporary().unwrap();
+        let outer_entry = outer.entry("sha256:outer").unwrap();
+        fs::write(outer_entry.image_tar(), "tar").unwrap();
+
+        let inner = Workspace::temporary().unwrap();
+        let inner_entry = inner.entry("sha256:inner").unwrap();
+        drop(inner);
+        assert!(!inner_entry.image_tar().parent().unwrap().exists());
+        assert!(outer_entry.image_tar().exists());
+
+        drop(outer);
+        assert!(!outer_entry.image_tar().parent().unwrap().exists());
+    }
+}
@@ -0,0 +1,53 @@
+// Runs the cake binary with a fake docker cli as the only runtime on PATH
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use std::process::{Command, Output};
+
+// Knows one image and can not save it
+const FAKE_DOCKER: &str = r#"#!/bin/sh
+case "$1" in
+image)
+    if [ "$3" = "app" ]; then
+        echo '[{"Id":"sha256:app","RepoTags":["app:latest"],"Os":"linux","Architecture":"amd64"}]'
+    else
+        echo '[]'
+        echo "Error: No such image: $3" >&2
+        exit 1
+    fi ;;
+save)
+    echo "Error response from daemon: no space left on device" >&2
+    exit 1 ;;
+esac
+"#;
+
+fn cake(dir: &Path, image: &str) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_cake"))
+        .arg(image)
+        .env("PATH", dir.join("bin"))
+        // No docker socket, so the runtimes on PATH are looked up
+        .env(
+            "DOCKER_HOST",
+            format!("unix://{}", dir.join("docker.sock").display()),
+        )
+        .env("XDG_CACHE_HOME", dir.join("cache"))
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn detects_docker_on_path() {
+    let dir = tempfile::tempdir().unwrap();
+    let docker = dir.path().join("bin/docker");
+    fs::create_dir(dir.path().join("bin")).unwrap();
+    fs::write(&docker, FAKE_DOCKER).unwrap();
+    fs::set_permissions(&docker, fs::Permissions::from_mode(0o755)).unwrap();
+
+    let missing = cake(dir.path(), "web");
+    assert_eq!(missing.status.code(), Some(3));
+    assert!(String::from_utf8_lossy(&missing.stderr).contains("No such image: web"));
+
+    let failed = cake(dir.path(), "app");
+    assert_eq!(failed.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&failed.stderr).contains("no space left on device"));
+}